@@ -1,15 +1,19 @@
-use core::panic;
 use std::error::Error;
 
 use chrono::Local;
 use clap::Args;
-use image::ImageBuffer;
 
 use crate::utils::{
-    covers::{get_album_covers, AlbumCoverData, AlbumCoverDataFilter},
-    data::{validate_img_filename, sum_rgb}
+    config::Config,
+    covers::{get_album_covers, AlbumCoverData},
+    data::{validate_img_filename, sum_rgb},
+    error::{report_skipped, MicError},
+    filters::Predicate,
+    songs::SongDataFilter,
 };
 
+use super::filter::FilterArgs;
+
 #[derive(Args)]
 pub struct AccgArgs {
     /// Name of the Artist ex. "Green Day"
@@ -44,6 +48,9 @@ pub struct AccgArgs {
     #[clap(short = 'w', long = "week")]
     week: bool,
 
+    #[clap(flatten)]
+    filter: FilterArgs,
+
     /// Filename for the playlist output without extension
     name: Option<String>,
 }
@@ -52,6 +59,11 @@ const MAX_WIDTH: u32 = 3840;
 const MAX_HEIGHT: u32 = 2160;
 
 fn create_collage(images: Vec<AlbumCoverData>) -> Result<image::DynamicImage, Box<dyn Error>> {
+    // An empty grid has no columns to size
+    if images.is_empty() {
+        return Err("no covers match".into());
+    }
+
     // Get the dimensions of the first image to determine the size of the collage
     let grid_size = (images.len() as f64).sqrt().ceil() as usize;
     let rows = (images.len() as f64 / grid_size as f64).ceil() as usize;
@@ -63,8 +75,9 @@ fn create_collage(images: Vec<AlbumCoverData>) -> Result<image::DynamicImage, Bo
     let size = col_size.min(row_size);
 
     // Create a new image for the collage
-    let center_origin = cols as u32 * size as u32;
+    let center_origin = cols as u32 * size;
     let mut collage = image::DynamicImage::new_rgb8(center_origin, MAX_HEIGHT);
+    let mut skipped: Vec<MicError> = vec![];
 
     for y in 0..rows {
         for x in 0..cols {
@@ -73,23 +86,25 @@ fn create_collage(images: Vec<AlbumCoverData>) -> Result<image::DynamicImage, Bo
                 break;
             }
 
-            let img = images[index].clone();
+            // Thumbnails are only read from disk once they're placed
+            let thumbnail = match images[index].image.load() {
+                Ok(thumbnail) => thumbnail,
+                Err(e) => {
+                    skipped.push(e);
+                    continue;
+                },
+            };
 
             let pos_x: i64 = size as i64 * x as i64;
             let pos_y: i64 = size as i64 * y as i64;
-            let gen_img = image::DynamicImage::ImageRgb8(
-                ImageBuffer::from_vec(
-                    img.image.width, 
-                    img.image.width, 
-                    img.image.pixels
-                ).unwrap(),
-            )
-            .resize(size, size, image::imageops::FilterType::Triangle);
+            let gen_img = thumbnail.resize(size, size, image::imageops::FilterType::Triangle);
 
             image::imageops::overlay(&mut collage, &gen_img, pos_x, pos_y);
         }
     }
 
+    report_skipped(&skipped);
+
     Ok(collage)
 }
 
@@ -114,69 +129,72 @@ fn generate_collage_name(args: &AccgArgs) -> String {
         subject = "decade"
     }
 
-    format!("{}", subject)
+    subject.to_string()
 }
 
 fn generate_filename(args: &AccgArgs) -> Result<String, Box<dyn Error>> {
-    let filename: String;
-
-    let collage_type = generate_collage_name(&args);
-    if args.genre.is_some() {
-        filename = format!("{}.png", args.genre.as_ref().unwrap().replace([',', ' '], "-").to_lowercase())
-    } else if args.moods.is_some() {
-        filename = format!("{}.png", args.moods.as_ref().unwrap().replace([',', ' '], "-").to_lowercase())
-    } else if args.artist.is_some() {
-        filename = format!("{}.png", args.artist.as_ref().unwrap().replace([',', ' '], "-").to_lowercase())
+    let collage_type = generate_collage_name(args);
+    let filename = if let Some(genre) = &args.genre {
+        format!("{}.png", genre.replace([',', ' '], "-").to_lowercase())
+    } else if let Some(moods) = &args.moods {
+        format!("{}.png", moods.replace([',', ' '], "-").to_lowercase())
+    } else if let Some(artist) = &args.artist {
+        format!("{}.png", artist.replace([',', ' '], "-").to_lowercase())
     } else {
-        filename = format!("{}-{}.png", Local::now().format("%Y%m%d%H%M%S"), collage_type)
-
-    }
+        format!("{}-{}.png", Local::now().format("%Y%m%d%H%M%S"), collage_type)
+    };
 
     Ok(filename)
 }
 
-pub fn accg(args: AccgArgs) -> Result<(), Box<dyn Error>> {
+pub fn accg(args: AccgArgs, config: &Config) -> Result<(), Box<dyn Error>> {
     let filename = match args.name {
         Some(name) => {
             if !validate_img_filename(&name)? {
-                panic!("Invalid filename")
+                return Err(MicError::image(&name, "collage name must end in .png or .jpg").into());
             }
 
             name
         },
         None => {
             let filename = generate_filename(&args)?;
-            format!("/home/andrew/picx/accg/{}", filename)
+            let output_dir = &config.collage.output_dir;
+
+            std::fs::create_dir_all(output_dir)?;
+            output_dir.join(filename).to_string_lossy().to_string()
         }
     };
-    let covers = get_album_covers()?;
-
-    let filter = AlbumCoverDataFilter {
+    let filter = SongDataFilter {
+        day: args.day.map(u32::from),
         month: args.month,
         year: args.year,
-        genre: args.genre,
-        moods: args.moods,
-        artist: args.artist,
+        genre: args.filter.list(args.genre.as_deref())?,
+        moods: args.filter.list(args.moods.as_deref())?,
+        artist: args.filter.text(args.artist.as_deref())?,
         decade: args.decade,
         week: args.week,
+        date_field: config.library.date_field,
         ..Default::default()
-    };
+    }
+    .and(args.filter.predicate(config)?);
+
+    let covers = get_album_covers(config)?;
 
     let mut filtered = filter.filter(covers);
     // filtered.sort_by(|a, b| a.album_data._album_artist.cmp(&b.album_data._album_artist));
 
     filtered.sort_by(|a, b| {
         let sum_a: u32 = a.image.dominant_colors.iter()
-            .map(|color| sum_rgb(color)).sum();
+            .map(sum_rgb).sum();
         let sum_b: u32 = b.image.dominant_colors.iter()
-            .map(|color| sum_rgb(color)).sum();
+            .map(sum_rgb).sum();
 
         sum_a.cmp(&sum_b)
     });
 
     let collage = create_collage(filtered)?;
 
-    collage.save(&filename).expect("Failed to save new image");
+    collage.save(&filename).map_err(|e| MicError::image(&filename, e))?;
 
     println!("{}", filename);
 
@@ -0,0 +1,268 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Subcommand};
+use serde::Serialize;
+
+use crate::utils::{
+    cache::{clear_cache, export_cache, import_cache, load_cache, CacheData, CacheRecord, Fingerprint},
+    config::Config,
+    covers::{clear_thumbnails, get_album_covers, thumbnail_usage, AlbumCoverData},
+    output::Report,
+    songs::{get_songs, load_song_tag, SongData},
+    store::Store,
+};
+
+#[derive(Args)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    commands: CacheCommands,
+}
+
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Show entry counts, sizes, stale entries and orphans of each cache
+    Status,
+
+    /// Throw away the song cache and scan the library again
+    Rebuild(RebuildArgs),
+
+    /// Re-read every cached file and report entries that no longer match
+    Verify,
+
+    /// Empty every cache
+    Clear,
+
+    /// Write a cache to a JSON file
+    Export(TransferArgs),
+
+    /// Merge a JSON file written by export into the cache
+    Import(TransferArgs),
+}
+
+#[derive(Args)]
+pub struct RebuildArgs {
+    /// Also rebuild the cover cache, this decodes every cover again
+    #[clap(short = 'c', long = "covers")]
+    covers: bool,
+}
+
+#[derive(Args)]
+pub struct TransferArgs {
+    /// Use the cover cache instead of the song cache
+    #[clap(short = 'c', long = "covers")]
+    covers: bool,
+
+    /// JSON file to write or read
+    file: PathBuf,
+}
+
+pub fn cache_command(args: CacheArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    match args.commands {
+        CacheCommands::Status => cache_status(config)?,
+        CacheCommands::Rebuild(args) => cache_rebuild(args, config)?,
+        CacheCommands::Verify => cache_verify(config)?,
+        CacheCommands::Clear => cache_clear()?,
+        CacheCommands::Export(args) => cache_export(args)?,
+        CacheCommands::Import(args) => cache_import(args)?,
+    };
+
+    Ok(())
+}
+
+struct CacheHealth {
+    entries: usize,
+    stale: usize,
+    orphans: usize,
+}
+
+/// Counts entries whose file changed (stale) or disappeared (orphans)
+fn check_cache<T>(cache: &CacheData<T>, config: &Config) -> CacheHealth {
+    let mut health = CacheHealth { entries: cache.data.len(), stale: 0, orphans: 0 };
+
+    for (filename, entry) in cache.data.iter() {
+        let with_hash = config.cache.hash || entry.fingerprint.hash.is_some();
+
+        match Fingerprint::of(Path::new(filename), with_hash) {
+            Ok(current) if entry.fingerprint.is_fresh(&current) => {},
+            Ok(_) => health.stale += 1,
+            Err(_) => health.orphans += 1,
+        }
+    }
+
+    health
+}
+
+fn format_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, units[unit])
+}
+
+pub fn cache_status(config: &Config) -> Result<(), Box<dyn Error>> {
+    let store = Store::open()?;
+    let songs = check_cache(&load_cache::<SongData>()?, config);
+    let covers = check_cache(&load_cache::<AlbumCoverData>()?, config);
+
+    let mut report = Report::new(&[
+        ("cache", "Cache"),
+        ("entries", "Entries"),
+        ("size", "Size"),
+        ("stale", "Stale"),
+        ("orphans", "Orphans"),
+    ]);
+
+    // Cover rows only hold the palette, most of their space is taken by the thumbnails
+    let (_, cover_bytes) = store.usage::<AlbumCoverData>()?;
+    let thumbnail_bytes = thumbnail_usage()?;
+
+    for (name, health, bytes) in [
+        (SongData::TABLE, songs, store.usage::<SongData>()?.1),
+        (AlbumCoverData::TABLE, covers, cover_bytes + thumbnail_bytes),
+    ] {
+        report.row(vec![
+            name.into(),
+            health.entries.into(),
+            format_size(bytes).into(),
+            health.stale.into(),
+            health.orphans.into(),
+        ]);
+    }
+
+    report.print(config.output)?;
+
+    Ok(())
+}
+
+pub fn cache_rebuild(args: RebuildArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    clear_cache::<SongData>()?;
+    let songs = get_songs(config)?;
+    println!("{}: {} entries", SongData::TABLE, songs.len());
+
+    if args.covers {
+        clear_cache::<AlbumCoverData>()?;
+        clear_thumbnails()?;
+        let covers = get_album_covers(config)?;
+        println!("{}: {} entries", AlbumCoverData::TABLE, covers.len());
+    }
+
+    Ok(())
+}
+
+pub fn cache_verify(config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut mismatches: Vec<(String, String)> = vec![];
+
+    let songs = load_cache::<SongData>()?;
+    for (filename, entry) in songs.data.iter() {
+        if let Some(reason) = verify_entry(filename, entry.fingerprint.clone(), &entry.value, config, |f| {
+            load_song_tag(f).map_err(|e| e.into())
+        }) {
+            mismatches.push((filename.clone(), reason));
+        }
+    }
+
+    let covers = load_cache::<AlbumCoverData>()?;
+    for (filename, entry) in covers.data.iter() {
+        // Covers only need to decode and still have their thumbnail, the palette is derived from the image
+        if let Some(reason) = verify_entry(filename, entry.fingerprint.clone(), &entry.value, config, |f| {
+            image::open(f).map_err(|e| -> Box<dyn Error> { e.into() })?;
+            entry.value.image.load()?;
+
+            Ok(entry.value.clone())
+        }) {
+            mismatches.push((filename.clone(), reason));
+        }
+    }
+
+    if mismatches.is_empty() && config.output.is_table() {
+        println!("{} entries verified, no mismatches", songs.data.len() + covers.data.len());
+        return Ok(());
+    }
+
+    mismatches.sort();
+
+    let mut report = Report::new(&[("file", "File"), ("problem", "Problem")]);
+
+    for (filename, reason) in mismatches {
+        report.row(vec![filename.into(), reason.into()]);
+    }
+
+    report.print(config.output)?;
+
+    Ok(())
+}
+
+/// Re-probes a single entry, returns why it no longer matches the file on disk
+fn verify_entry<T, F>(filename: &str, cached: Fingerprint, value: &T, config: &Config, probe: F) -> Option<String>
+where
+    T: Serialize,
+    F: Fn(&str) -> Result<T, Box<dyn Error>>,
+{
+    let with_hash = config.cache.hash || cached.hash.is_some();
+    let current = match Fingerprint::of(Path::new(filename), with_hash) {
+        Ok(current) => current,
+        Err(_) => return Some("file no longer exists".to_string()),
+    };
+
+    if current.size != cached.size {
+        return Some(format!("size changed {} -> {}", cached.size, current.size));
+    }
+
+    if current.mtime != cached.mtime {
+        return Some("modified since cached".to_string());
+    }
+
+    if !cached.is_fresh(&current) {
+        return Some("contents changed".to_string());
+    }
+
+    match probe(filename) {
+        Ok(fresh) => {
+            let same = serde_json::to_value(&fresh).ok() == serde_json::to_value(value).ok();
+
+            (!same).then(|| "cached tags differ from file".to_string())
+        },
+        Err(e) => Some(format!("unreadable, {}", e)),
+    }
+}
+
+pub fn cache_clear() -> Result<(), Box<dyn Error>> {
+    clear_cache::<SongData>()?;
+    clear_cache::<AlbumCoverData>()?;
+    clear_thumbnails()?;
+
+    println!("Cleared {} and {}", SongData::TABLE, AlbumCoverData::TABLE);
+
+    Ok(())
+}
+
+pub fn cache_export(args: TransferArgs) -> Result<(), Box<dyn Error>> {
+    let count = match args.covers {
+        true => export_cache::<AlbumCoverData>(&args.file)?,
+        false => export_cache::<SongData>(&args.file)?,
+    };
+
+    println!("Exported {} entries to {}", count, args.file.display());
+
+    Ok(())
+}
+
+pub fn cache_import(args: TransferArgs) -> Result<(), Box<dyn Error>> {
+    let count = match args.covers {
+        true => import_cache::<AlbumCoverData>(&args.file)?,
+        false => import_cache::<SongData>(&args.file)?,
+    };
+
+    println!("Imported {} entries from {}", count, args.file.display());
+
+    Ok(())
+}
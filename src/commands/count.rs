@@ -1,15 +1,30 @@
-use chrono::Datelike;
 use clap::{Args, Subcommand};
-use core::fmt;
-use prettytable::{format, row, Row, Table};
-use std::{collections::HashMap, error::Error};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    hash::Hash,
+};
 
 use crate::utils::{
+    config::Config,
     data::{array_truncate, hashmap_to_vec_truple},
-    date::parse_string_to_datetime,
-    songs::{get_albums, get_songs, phrases_to_words, SongData, SongDataFilter},
+    albums::{get_albums, group_albums},
+    chart::bar_chart,
+    error::{report_skipped, MicError},
+    filters::Predicate,
+    lyrics::Tokenizer,
+    output::Report,
+    query::{Field, GroupKey},
+    songs::{get_songs, SongData, SongDataFilter},
 };
 
+use super::filter::FilterArgs;
+
+/// Longest run of empty years drawn in the years chart, a stray year 0 shouldn't add 2000 lines
+const MAX_YEAR_GAP: i32 = 10;
+
 #[derive(Args)]
 pub struct CountArgs {
     /// Filter by Day of release
@@ -40,6 +55,9 @@ pub struct CountArgs {
     #[clap(short = 'l', long = "list")]
     list: bool,
 
+    #[clap(flatten)]
+    filter: FilterArgs,
+
     /// Filter by Year of release
     #[clap(subcommand)]
     commands: Option<CountCommands>,
@@ -58,44 +76,71 @@ enum CountCommands {
 
     /// What records to play based on release ranges
     Words(WordsArgs),
+
+    /// Count by any tag ex. label, composer, album_artist, decade, month, weekday
+    By(ByArgs),
+
+    /// Cross-tabulate two tags ex. genre by decade
+    Pivot(PivotArgs),
 }
 
-pub fn count_music(args: CountArgs) -> Result<(), Box<dyn Error>> {
+pub fn count_music(args: CountArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    // Flags given before a subcommand narrow it too, ex. `count --where 'year>1990' years`
+    let filter = SongDataFilter {
+        day: args.day.map(u32::from),
+        year: args.year,
+        month: args.month,
+        decade: args.decade,
+        moods: args.filter.list(args.moods.as_deref())?,
+        date_field: config.library.date_field,
+        ..Default::default()
+    }
+    .and(args.filter.predicate(config)?);
+    let album = args.album;
+
     match args.commands {
-        Some(CountCommands::Years(args)) => count_years(args)?,
-        Some(CountCommands::Genres(args)) => count_genres(args)?,
-        Some(CountCommands::Moods(args)) => count_moods(args)?,
-        Some(CountCommands::Words(args)) => count_words(args)?,
-        _ => count_general(args)?,
+        Some(CountCommands::Years(mut args)) => {
+            args.album |= album;
+            count_years(args, config, &filter)?
+        },
+        Some(CountCommands::Genres(mut args)) => {
+            args.album |= album;
+            count_genres(args, config, &filter)?
+        },
+        Some(CountCommands::Moods(mut args)) => {
+            args.album |= album;
+            count_moods(args, config, &filter)?
+        },
+        Some(CountCommands::Words(mut args)) => {
+            args.album |= album;
+            count_words(args, config, &filter)?
+        },
+        Some(CountCommands::By(mut args)) => {
+            args.album |= album;
+            count_by(args, config, &filter)?
+        },
+        Some(CountCommands::Pivot(mut args)) => {
+            args.album |= album;
+            count_pivot(args, config, &filter)?
+        },
+        None => count_general(album, config, &filter)?,
     };
 
     Ok(())
 }
 
-pub fn count_general(args: CountArgs) -> Result<(), Box<dyn Error>> {
-    let songs: Vec<SongData> = get_songs_or_albums(
-        args.album,
-        SongDataFilter {
-            year: args.year,
-            month: args.month,
-            moods: args.moods,
-            ..Default::default()
-        },
-    );
+pub fn count_general(album: bool, config: &Config, filter: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let songs: Vec<SongData> = get_songs_or_albums(config, album, filter)?;
 
-    let headers = row!["Name", "Total"];
+    let headers = Report::new(&[("name", "Name"), ("total", "Total")]);
     let mut table_rows: Vec<(&str, usize)> = vec![];
 
-    let mut albums: Vec<String> = vec![];
+    let albums = group_albums(songs.clone());
     let mut artists: Vec<String> = vec![];
     let mut genres: Vec<String> = vec![];
     let mut moods: Vec<String> = vec![];
 
     for song in songs.clone() {
-        if !albums.contains(&song.album_title) {
-            albums.push(song.album_title);
-        }
-
         if !artists.contains(&song.track_artist) {
             artists.push(song.track_artist);
         }
@@ -113,7 +158,7 @@ pub fn count_general(args: CountArgs) -> Result<(), Box<dyn Error>> {
         }
     }
 
-    if !args.album {
+    if !album {
         table_rows.push(("Songs", songs.len()));
     }
     table_rows.push(("Albums", albums.len()));
@@ -121,7 +166,7 @@ pub fn count_general(args: CountArgs) -> Result<(), Box<dyn Error>> {
     table_rows.push(("Genres", genres.len()));
     table_rows.push(("Moods", moods.len()));
 
-    print_table::<&str, usize>(headers, table_rows);
+    print_table::<&str>(config, headers, table_rows, false)?;
 
     Ok(())
 }
@@ -151,33 +196,49 @@ pub struct YearsArgs {
     /// Apply flag to collect by albums rather than songs
     #[clap(short = 'a', long = "album")]
     album: bool,
+
+    /// Draw the counts as a bar chart
+    #[clap(short = 'c', long = "chart")]
+    chart: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
 }
 
-pub fn count_years(args: YearsArgs) -> Result<(), Box<dyn Error>> {
-    let headers = get_count_headers("Years", args.album);
+pub fn count_years(args: YearsArgs, config: &Config, parent: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let headers = get_count_headers("year", "Years", args.album);
     let songs: Vec<SongData> = get_songs_or_albums(
+        config,
         args.album,
         SongDataFilter {
-            genre: args.genre,
+            genre: args.filter.list(args.genre.as_deref())?,
+            day: args.day.map(u32::from),
+            year: args.year,
             decade: args.decade,
             month: args.month,
+            date_field: config.library.date_field,
             ..Default::default()
-        },
-    );
+        }
+        .and(args.filter.predicate(config)?)
+        .and(parent),
+    )?;
 
-    let mut years_hash: HashMap<i32, usize> = HashMap::new();
+    let mut skipped: Vec<MicError> = vec![];
+    let years = songs.iter().flat_map(|song| group_keys(Field::Year, song, config, &mut skipped));
+    let mut vec_years = tally(years.filter_map(|key| match key {
+        GroupKey::Number(year) => Some(year as i32),
+        _ => None,
+    }));
 
-    for song in songs.iter() {
-        let dt = parse_string_to_datetime(&song.recording_date).unwrap();
-        let count = years_hash.entry(dt.year()).or_insert(0);
+    report_skipped(&skipped);
 
-        *count += 1;
-    }
+    vec_years.sort();
 
-    let mut vec_years = hashmap_to_vec_truple::<i32, usize>(years_hash);
+    if args.chart {
+        vec_years = fill_year_gaps(vec_years);
+    }
 
-    vec_years.sort();
-    print_table::<i32, usize>(headers, vec_years);
+    print_table::<i32>(config, headers, vec_years, args.chart)?;
 
     Ok(())
 }
@@ -203,36 +264,35 @@ pub struct GenreArgs {
     /// Apply flag to collect by albums rather than songs
     #[clap(short = 'a', long = "album")]
     album: bool,
+
+    /// Draw the counts as a bar chart
+    #[clap(short = 'c', long = "chart")]
+    chart: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
 }
 
-pub fn count_genres(args: GenreArgs) -> Result<(), Box<dyn Error>> {
-    let headers = get_count_headers("Genres", args.album);
+pub fn count_genres(args: GenreArgs, config: &Config, parent: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let headers = get_count_headers("genre", "Genres", args.album);
     let songs: Vec<SongData> = get_songs_or_albums(
+        config,
         args.album,
         SongDataFilter {
+            day: args.day.map(u32::from),
             year: args.year,
             month: args.month,
             decade: args.decade,
+            date_field: config.library.date_field,
             ..Default::default()
-        },
-    );
-
-    let mut genre_hash: HashMap<String, i32> = HashMap::new();
-
-    for song in songs.clone() {
-        for genre in song.genre {
-            let count = genre_hash.entry(genre).or_insert(0);
-
-            *count += 1;
         }
-    }
+        .and(args.filter.predicate(config)?)
+        .and(parent),
+    )?;
 
-    let mut vec_genres = hashmap_to_vec_truple::<String, i32>(genre_hash);
+    let vec_genres = tally(songs.into_iter().flat_map(|song| song.genre));
 
-    vec_genres.sort_by(|(a_key, a_val), (b_key, b_val)| {
-        b_val.cmp(&a_val).then_with(|| a_key.cmp(&b_key))
-    });
-    print_table::<String, i32>(headers, vec_genres);
+    print_table::<String>(config, headers, vec_genres, args.chart)?;
 
     Ok(())
 }
@@ -262,39 +322,45 @@ pub struct WordsArgs {
     /// Return on top numbers
     #[clap(short = 'l', long = "length")]
     length: Option<usize>,
+
+    /// Count phrases of 2 or 3 words instead of single words
+    #[clap(short = 'n', long = "ngram", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=3))]
+    ngram: u8,
+
+    /// Count words by their stem so "loving" and "loved" count as "love"
+    #[clap(long = "stem")]
+    stem: bool,
+
+    /// Draw the counts as a bar chart
+    #[clap(short = 'c', long = "chart")]
+    chart: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
 }
 
-pub fn count_words(args: WordsArgs) -> Result<(), Box<dyn Error>> {
-    let headers = get_count_headers("Moods", args.album);
+pub fn count_words(args: WordsArgs, config: &Config, parent: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let headers = get_count_headers("word", "Words", args.album);
     let songs: Vec<SongData> = get_songs_or_albums(
+        config,
         args.album,
         SongDataFilter {
+            day: args.day.map(u32::from),
             year: args.year,
             month: args.month,
+            decade: args.decade,
+            date_field: config.library.date_field,
             ..Default::default()
-        },
-    );
-    let mut words_hash: HashMap<String, usize> = HashMap::new();
-
-    for song in songs.clone() {
-        for phrases in song.lyrics {
-            let cleaned = phrases_to_words(phrases);
-
-            for word in cleaned {
-                words_hash.entry(word).and_modify(|count| *count += 1).or_insert(1);
-            }
         }
-    }
+        .and(args.filter.predicate(config)?)
+        .and(parent),
+    )?;
+    let tokenizer = Tokenizer::new(&config.lyrics, args.stem);
+    let mut vec_words = tally(songs.iter().flat_map(|song| tokenizer.tokens(song, args.ngram as usize)));
 
-    let mut vec_moods = hashmap_to_vec_truple::<String, usize>(words_hash);
+    array_truncate(&mut vec_words, args.length);
 
-    vec_moods.sort_by(|(a_key, a_val), (b_key, b_val)| {
-        b_val.cmp(&a_val).then_with(|| a_key.cmp(&b_key))
-    });
-
-    array_truncate(&mut vec_moods, args.length);
-
-    print_table::<String, usize>(headers, vec_moods.clone());
+    print_table::<String>(config, headers, vec_words, args.chart)?;
 
     Ok(())
 }
@@ -328,69 +394,434 @@ pub struct MoodArgs {
     /// Output items as list output
     #[clap(short = 'L', long = "list")]
     list: bool,
+
+    /// Draw the counts as a bar chart
+    #[clap(short = 'c', long = "chart")]
+    chart: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
 }
 
-pub fn count_moods(args: MoodArgs) -> Result<(), Box<dyn Error>> {
-    let headers = get_count_headers("Moods", args.album);
+pub fn count_moods(args: MoodArgs, config: &Config, parent: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let headers = get_count_headers("mood", "Moods", args.album);
     let songs: Vec<SongData> = get_songs_or_albums(
+        config,
         args.album,
         SongDataFilter {
+            day: args.day.map(u32::from),
             year: args.year,
             month: args.month,
+            decade: args.decade,
+            date_field: config.library.date_field,
             ..Default::default()
-        },
-    );
-    let mut mood_hash: HashMap<String, usize> = HashMap::new();
-
-    for song in songs.clone() {
-        for mood in song.mood {
-            mood_hash.entry(mood).and_modify(|count| *count += 1).or_insert(0);
         }
-    }
-
-    let mut vec_moods = hashmap_to_vec_truple::<String, usize>(mood_hash);
-
-    vec_moods.sort_by(|(a_key, a_val), (b_key, b_val)| {
-        b_val.cmp(&a_val).then_with(|| a_key.cmp(&b_key))
-    });
+        .and(args.filter.predicate(config)?)
+        .and(parent),
+    )?;
+    let mut vec_moods = tally(songs.into_iter().flat_map(|song| song.mood));
 
     array_truncate(&mut vec_moods, args.length);
 
-    if args.list {
+    // Plain names in a terminal, a single column report in any other format
+    if args.list && config.output.is_table() {
         for mood in vec_moods {
             println!("{}", mood.0)
         }
+    } else if args.list {
+        let mut report = Report::new(&[("mood", "Moods")]);
 
+        for (mood, _) in vec_moods {
+            report.row(vec![mood.into()]);
+        }
+
+        report.print(config.output)?;
     } else {
-        print_table::<String, usize>(headers, vec_moods);
+        print_table::<String>(config, headers, vec_moods, args.chart)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct ByArgs {
+    /// Tag to count by, any --where field ex. label, producer, language, album_artist, decade, month, weekday
+    field: String,
+
+    /// Apply flag to collect by albums rather than songs
+    #[clap(short = 'a', long = "album")]
+    album: bool,
+
+    /// Return on top numbers
+    #[clap(short = 'l', long = "length")]
+    length: Option<usize>,
+
+    /// Order groups by their count or by their value
+    #[clap(short = 's', long = "sort", value_enum, default_value_t)]
+    sort: GroupOrder,
+
+    /// Reverse the order
+    #[clap(short = 'r', long = "reverse")]
+    reverse: bool,
+
+    /// Draw the counts as a bar chart
+    #[clap(short = 'c', long = "chart")]
+    chart: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum GroupOrder {
+    /// Most common first
+    #[default]
+    Count,
+    /// Alphabetical or numeric
+    Value,
+}
+
+pub fn count_by(args: ByArgs, config: &Config, parent: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let field = Field::parse_group(&args.field)?;
+    let headers = get_count_headers(field.name(), &field.title(), args.album);
+    let songs: Vec<SongData> = get_songs_or_albums(config, args.album, args.filter.predicate(config)?.and(parent))?;
+
+    let mut skipped: Vec<MicError> = vec![];
+    let keys = songs.iter().flat_map(|song| group_keys(field, song, config, &mut skipped));
+    let mut groups = tally(keys);
+
+    report_skipped(&skipped);
+
+    if let GroupOrder::Value = args.sort {
+        groups.sort();
+    }
+
+    if args.reverse {
+        groups.reverse();
+    }
+
+    array_truncate(&mut groups, args.length);
+    print_table(config, headers, groups, args.chart)?;
+
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct PivotArgs {
+    /// Tag of the rows, any count by field ex. genre
+    #[clap(long = "rows")]
+    rows: String,
+
+    /// Tag of the columns ex. decade
+    #[clap(long = "cols")]
+    cols: String,
+
+    /// Filter by Decade of release
+    #[clap(short = 'D', long = "decade")]
+    decade: Option<u16>,
+
+    /// Filter by Year of release
+    #[clap(short = 'y', long = "year")]
+    year: Option<i32>,
+
+    /// Filter by Genre(s) (separate by comma)
+    #[clap(short = 'g', long = "genre")]
+    genre: Option<String>,
+
+    /// Mood names ex. "eclectic,warm,dark"
+    #[clap(short = 'M', long = "moods")]
+    moods: Option<String>,
+
+    /// Apply flag to collect by albums rather than songs
+    #[clap(short = 'a', long = "album")]
+    album: bool,
+
+    /// Keep the rows with the highest totals
+    #[clap(short = 'l', long = "length")]
+    length: Option<usize>,
+
+    /// Show each cell as a percentage of its row, its column or the whole table
+    #[clap(short = 'p', long = "percent", value_enum)]
+    percent: Option<PivotShare>,
+
+    /// Add a total column and row
+    #[clap(short = 't', long = "totals")]
+    totals: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum PivotShare {
+    Row,
+    Col,
+    Total,
+}
+
+pub fn count_pivot(args: PivotArgs, config: &Config, parent: &impl Predicate) -> Result<(), Box<dyn Error>> {
+    let row_field = Field::parse_group(&args.rows)?;
+    let col_field = Field::parse_group(&args.cols)?;
+    let songs: Vec<SongData> = get_songs_or_albums(
+        config,
+        args.album,
+        SongDataFilter {
+            year: args.year,
+            decade: args.decade,
+            genre: args.filter.list(args.genre.as_deref())?,
+            moods: args.filter.list(args.moods.as_deref())?,
+            date_field: config.library.date_field,
+            ..Default::default()
+        }
+        .and(args.filter.predicate(config)?)
+        .and(parent),
+    )?;
+
+    // A song counts once in every row and column its tags put it in
+    let mut skipped: Vec<MicError> = vec![];
+    let keys: Vec<(Vec<GroupKey>, Vec<GroupKey>)> = songs
+        .iter()
+        .map(|song| {
+            let cols = group_keys(col_field, song, config, &mut skipped);
+            // Reading the rows of a song already skipped would report it twice
+            let rows = match cols.is_empty() {
+                true => vec![],
+                false => group_keys(row_field, song, config, &mut skipped),
+            };
+
+            (rows, cols)
+        })
+        .collect();
+
+    report_skipped(&skipped);
+
+    let mut pivot = Pivot::new(keys);
+    let mut col_keys: Vec<&GroupKey> = pivot.cols.keys().collect();
+
+    col_keys.sort();
+    array_truncate(&mut pivot.rows, args.length);
+
+    let share = |count: usize, row_total: usize, col_total: usize| -> Value {
+        match args.percent {
+            None => count.into(),
+            Some(PivotShare::Row) => percent(count, row_total).into(),
+            Some(PivotShare::Col) => percent(count, col_total).into(),
+            Some(PivotShare::Total) => percent(count, pivot.total).into(),
+        }
+    };
+
+    // Value columns are keyed "decade:1990" so no value can take the key of the rows field or of the total
+    let keys: Vec<String> = col_keys.iter().map(|col| format!("{}:{}", col_field.name(), col)).collect();
+    let titles: Vec<String> = col_keys.iter().map(|col| col.to_string()).collect();
+    let row_title = row_field.title();
+    let mut columns: Vec<(&str, &str)> = vec![(row_field.name(), &row_title)];
+    columns.extend(keys.iter().map(|key| key.as_str()).zip(titles.iter().map(|title| title.as_str())));
+
+    if args.totals {
+        columns.push(("total", "Total"));
+    }
+
+    let mut report = Report::new(&columns);
+
+    for (row, row_total) in &pivot.rows {
+        let mut cells_of_row: Vec<Value> = vec![row.clone().into()];
+
+        for col in &col_keys {
+            cells_of_row.push(share(pivot.cell(row, col), *row_total, pivot.cols[*col]));
+        }
+
+        if args.totals {
+            cells_of_row.push(share(*row_total, *row_total, pivot.total));
+        }
+
+        report.row(cells_of_row);
+    }
+
+    if args.totals {
+        let mut totals_row: Vec<Value> = vec!["Total".into()];
+        totals_row.extend(col_keys.iter().map(|col| share(pivot.cols[*col], pivot.total, pivot.cols[*col])));
+        totals_row.push(share(pivot.total, pivot.total, pivot.total));
+
+        report.row(totals_row);
     }
 
+    report.print(config.output)?;
+
     Ok(())
 }
 
-fn print_table<K: fmt::Display, T: fmt::Display>(headers: Row, data: Vec<(K, T)>) {
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_LINESEP);
-    table.add_row(headers);
+/// Songs in each cell of a pivot, totals count distinct songs so a song with two genres is one song of its column
+struct Pivot {
+    cells: HashMap<(GroupKey, GroupKey), usize>,
+    /// Most songs first
+    rows: Vec<(GroupKey, usize)>,
+    cols: HashMap<GroupKey, usize>,
+    total: usize,
+}
+
+impl Pivot {
+    /// From the row and column keys of each song, songs missing either are left out
+    fn new(songs: Vec<(Vec<GroupKey>, Vec<GroupKey>)>) -> Pivot {
+        let songs: Vec<(HashSet<GroupKey>, HashSet<GroupKey>)> = songs
+            .into_iter()
+            .filter(|(rows, cols)| !rows.is_empty() && !cols.is_empty())
+            .map(|(rows, cols)| (rows.into_iter().collect(), cols.into_iter().collect()))
+            .collect();
+
+        let pairs = songs.iter().flat_map(|(rows, cols)| {
+            rows.iter().flat_map(move |row| cols.iter().map(move |col| (row.clone(), col.clone())))
+        });
+
+        Pivot {
+            cells: tally(pairs).into_iter().collect(),
+            rows: tally(songs.iter().flat_map(|(rows, _)| rows.iter().cloned())),
+            cols: tally(songs.iter().flat_map(|(_, cols)| cols.iter().cloned())).into_iter().collect(),
+            total: songs.len(),
+        }
+    }
+
+    fn cell(&self, row: &GroupKey, col: &GroupKey) -> usize {
+        self.cells.get(&(row.clone(), col.clone())).copied().unwrap_or(0)
+    }
+}
+
+/// Percentage rounded to a tenth
+fn percent(count: usize, whole: usize) -> f64 {
+    (count as f64 * 1000.0 / whole.max(1) as f64).round() / 10.0
+}
+
+/// Keys a song is counted under, a song whose tag can't be read is skipped and reported
+pub fn group_keys(field: Field, song: &SongData, config: &Config, skipped: &mut Vec<MicError>) -> Vec<GroupKey> {
+    match field.group_keys(song, config.library.date_field) {
+        Ok(keys) => keys,
+        Err(e) => {
+            skipped.push(e.in_file(&song.filename));
+            vec![]
+        },
+    }
+}
+
+/// Years without songs still take a line so gaps show in the chart, longer gaps than MAX_YEAR_GAP are left out
+fn fill_year_gaps(years: Vec<(i32, usize)>) -> Vec<(i32, usize)> {
+    let mut filled: Vec<(i32, usize)> = vec![];
+
+    for (year, count) in years {
+        if let Some(&(last, _)) = filled.last() {
+            if year - last <= MAX_YEAR_GAP {
+                filled.extend((last + 1..year).map(|empty| (empty, 0)));
+            }
+        }
+
+        filled.push((year, count));
+    }
+
+    filled
+}
+
+/// Counts how often each key appears, most common first and ties by key
+fn tally<K: Hash + Eq + Ord>(keys: impl IntoIterator<Item = K>) -> Vec<(K, usize)> {
+    let mut counts: HashMap<K, usize> = HashMap::new();
+
+    for key in keys {
+        *counts.entry(key).or_insert(0) += 1;
+    }
+
+    let mut counted = hashmap_to_vec_truple::<K, usize>(counts);
+
+    counted.sort_by(|(a_key, a_val), (b_key, b_val)| {
+        b_val.cmp(a_val).then_with(|| a_key.cmp(b_key))
+    });
+
+    counted
+}
+
+/// Prints the counts in the chosen output format, or as bars when charted in a terminal table
+fn print_table<K: fmt::Display + Into<Value>>(
+    config: &Config,
+    mut report: Report,
+    data: Vec<(K, usize)>,
+    chart: bool,
+) -> Result<(), MicError> {
+    if chart && config.output.is_table() {
+        let rows: Vec<(String, usize)> = data.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+        println!("{}", bar_chart(&rows));
+        return Ok(());
+    }
 
     for (key, value) in data {
-        let row = row![key, value];
-        table.add_row(row);
+        report.row(vec![key.into(), value.into()]);
     }
 
-    table.printstd();
+    report.print(config.output)
 }
 
-fn get_songs_or_albums(album: bool, filters: SongDataFilter) -> Vec<SongData> {
-    filters.filter(match album {
-        true => get_albums().unwrap(),
-        false => get_songs().unwrap(),
+fn get_songs_or_albums(
+    config: &Config,
+    album: bool,
+    filters: impl Predicate,
+) -> Result<Vec<SongData>, Box<dyn Error>> {
+    // Albums are counted by their first track
+    Ok(match album {
+        true => filters.filter(get_albums(config)?).iter().map(|a| a.head().clone()).collect(),
+        false => filters.filter(get_songs(config)?),
     })
 }
 
-fn get_count_headers(count_name: &str, album: bool) -> Row {
+/// Columns of a count, keyed by what's counted and "songs" or "albums"
+fn get_count_headers(key: &str, count_name: &str, album: bool) -> Report {
     match album {
-        true => row![count_name, "# of Albums"],
-        false => row![count_name, "# of Songs"],
+        true => Report::new(&[(key, count_name), ("albums", "# of Albums")]),
+        false => Report::new(&[(key, count_name), ("songs", "# of Songs")]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_year_gaps, percent, tally, Pivot};
+    use crate::utils::query::GroupKey;
+
+    fn keys(values: &[&str]) -> Vec<GroupKey> {
+        values.iter().map(|v| GroupKey::Text(v.to_string())).collect()
+    }
+
+    #[test]
+    fn pivot_totals_count_distinct_songs() {
+        let pivot = Pivot::new(vec![
+            (keys(&["1990"]), keys(&["punk", "rock"])),
+            (keys(&["1990"]), keys(&["punk"])),
+            (keys(&["2000"]), keys(&["rock", "rock"])),
+            (keys(&["2000"]), keys(&[])),
+        ]);
+        let (punk, rock) = (&keys(&["punk"])[0], &keys(&["rock"])[0]);
+        let nineties = &keys(&["1990"])[0];
+
+        assert_eq!(pivot.cell(nineties, punk), 2);
+        assert_eq!(pivot.cell(nineties, rock), 1);
+        assert_eq!(pivot.rows, vec![(nineties.clone(), 2), (keys(&["2000"])[0].clone(), 1)]);
+        assert_eq!((pivot.cols[punk], pivot.cols[rock]), (2, 2));
+        assert_eq!(pivot.total, 3);
+    }
+
+    #[test]
+    fn rounds_percentages_to_a_tenth() {
+        assert_eq!(percent(1, 3), 33.3);
+        assert_eq!(percent(2, 3), 66.7);
+        assert_eq!(percent(0, 0), 0.0);
+    }
+
+    #[test]
+    fn tallies_most_common_first_then_by_key() {
+        let counted = tally(["metal", "punk", "art pop", "punk", "metal", "punk"]);
+
+        assert_eq!(counted, vec![("punk", 3), ("metal", 2), ("art pop", 1)]);
+        assert!(tally(Vec::<u8>::new()).is_empty());
+    }
+
+    #[test]
+    fn fills_only_short_year_gaps() {
+        let filled = fill_year_gaps(vec![(0, 1), (1990, 2), (1993, 1), (2010, 4)]);
+
+        assert_eq!(filled, vec![(0, 1), (1990, 2), (1991, 0), (1992, 0), (1993, 1), (2010, 4)]);
+        assert!(fill_year_gaps(vec![]).is_empty());
     }
 }
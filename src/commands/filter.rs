@@ -0,0 +1,219 @@
+use std::ops::Bound;
+
+use clap::Args;
+
+use crate::utils::{
+    config::Config,
+    date::{parse_date, parse_duration, parse_number, parse_period, parse_range, DateField, PartialDate, Range},
+    error::MicError,
+    filters::{AudioFilter, DateRangeFilter, DetailFilter, ExcludeFilter, MatchMode, Predicate, TextMatcher},
+    query::Query,
+};
+
+/// Filter flags shared by every command that lists songs or albums
+#[derive(Args)]
+pub struct FilterArgs {
+    /// How text values like artist, genre, track or lyric words compare with tags, case and accents are always ignored
+    #[clap(long = "match", value_enum, default_value_t)]
+    match_mode: MatchMode,
+
+    /// Boolean filter ex. "genre:metal and (year>=1990 and year<2000) and not mood:happy"
+    #[clap(long = "where")]
+    query: Option<String>,
+
+    /// Released on or after a date or the start of a period ex. 1994-05-10, 1994, "last 30 days"
+    #[clap(long = "since")]
+    since: Option<String>,
+
+    /// Released on or before a date or the end of a period ex. 1999-12-31, "this year"
+    #[clap(long = "until")]
+    until: Option<String>,
+
+    /// Range of release years ex. 1985..1994, 1985..<1995, 1990..
+    #[clap(long = "years")]
+    years: Option<String>,
+
+    /// Range of decades ex. 1980..1990 for the 80s and 90s
+    #[clap(long = "decades")]
+    decades: Option<String>,
+
+    /// Range of release dates ex. 1994-01-01..1994-06-30
+    #[clap(long = "dates")]
+    dates: Option<String>,
+
+    /// Release anniversary within a period ex. "this week", "next month", "last 2 weeks"
+    #[clap(long = "anniversary")]
+    anniversary: Option<String>,
+
+    /// Anyone credited in any role ex. "Steve Albini", several names separated by comma
+    #[clap(long = "credit")]
+    credit: Option<String>,
+
+    /// Record label ex. "Warp Records"
+    #[clap(long = "label")]
+    label: Option<String>,
+
+    /// Language tag ex. eng
+    #[clap(long = "language")]
+    language: Option<String>,
+
+    /// Catalog number ex. TG141
+    #[clap(long = "catalog")]
+    catalog: Option<String>,
+
+    /// Only lossless formats like flac, alac and wav
+    #[clap(long = "lossless")]
+    lossless: bool,
+
+    /// Codec or container ex. "opus", "flac,alac"
+    #[clap(long = "format")]
+    format: Option<String>,
+
+    /// Lowest bitrate in kbps ex. 256
+    #[clap(long = "min-bitrate")]
+    min_bitrate: Option<u32>,
+
+    /// Highest bitrate in kbps ex. 192, to find low quality rips
+    #[clap(long = "max-bitrate")]
+    max_bitrate: Option<u32>,
+
+    /// Lowest sample rate in Hz ex. 48000
+    #[clap(long = "min-sample-rate")]
+    min_sample_rate: Option<u32>,
+
+    /// Lowest bit depth ex. 24
+    #[clap(long = "min-bit-depth")]
+    min_bit_depth: Option<u8>,
+
+    /// Number of channels ex. 2
+    #[clap(long = "channels")]
+    channels: Option<u8>,
+
+    /// Range of track lengths ex. 2m..6m, 3:30.., ..90s
+    #[clap(long = "duration")]
+    duration: Option<String>,
+
+    /// Leave out genres ex. "black metal,christmas" (repeatable)
+    #[clap(long = "exclude-genre", value_delimiter = ',')]
+    exclude_genre: Vec<String>,
+
+    /// Leave out moods ex. "happy,upbeat" (repeatable)
+    #[clap(long = "exclude-mood", value_delimiter = ',')]
+    exclude_mood: Vec<String>,
+
+    /// Leave out track or album artists ex. "Various Artists,Unknown Artist" (repeatable)
+    #[clap(long = "exclude-artist", value_delimiter = ',')]
+    exclude_artist: Vec<String>,
+
+    /// Leave out songs whose lyrics contain words ex. "christmas,santa" (repeatable)
+    #[clap(long = "exclude-words", value_delimiter = ',')]
+    exclude_words: Vec<String>,
+}
+
+impl FilterArgs {
+    /// Compiles every flag into a single predicate
+    pub fn predicate(&self, config: &Config) -> Result<impl Predicate, MicError> {
+        let date_field = config.library.date_field;
+
+        // Option has an `and` of its own, the first one has to name the trait
+        Ok(Predicate::and(self.query(date_field)?, self.date_ranges(date_field)?)
+            .and(self.exclusions()?)
+            .and(self.details()?)
+            .and(self.audio()?))
+    }
+
+    fn audio(&self) -> Result<AudioFilter, MicError> {
+        let duration = match &self.duration {
+            Some(d) if !d.contains("..") => {
+                return Err(MicError::query(format!("--duration needs a range like 2m..6m, found \"{}\"", d)))
+            },
+            d => d.as_deref().map(|d| parse_range(d, parse_duration)).transpose()?,
+        };
+
+        Ok(AudioFilter {
+            lossless: self.lossless,
+            formats: self.list(self.format.as_deref())?,
+            bitrate: between(self.min_bitrate, self.max_bitrate),
+            sample_rate: between(self.min_sample_rate, None),
+            bit_depth: between(self.min_bit_depth, None),
+            channels: self.channels,
+            duration,
+        })
+    }
+
+    fn details(&self) -> Result<DetailFilter, MicError> {
+        Ok(DetailFilter {
+            credit: self.list(self.credit.as_deref())?,
+            label: self.text(self.label.as_deref())?,
+            language: self.text(self.language.as_deref())?,
+            catalog: self.text(self.catalog.as_deref())?,
+        })
+    }
+
+    /// Compiles a single value flag like --artist with the chosen --match mode
+    pub fn text(&self, value: Option<&str>) -> Result<Option<TextMatcher>, MicError> {
+        value.map(|v| TextMatcher::new(v, self.match_mode)).transpose()
+    }
+
+    /// Compiles a comma separated flag like --genre with the chosen --match mode
+    pub fn list(&self, value: Option<&str>) -> Result<Option<TextMatcher>, MicError> {
+        value.map(|v| TextMatcher::any_of(v, self.match_mode)).transpose()
+    }
+
+    fn exclusions(&self) -> Result<ExcludeFilter, MicError> {
+        let matcher = |values: &[String]| match values.is_empty() {
+            true => Ok(None),
+            false => TextMatcher::any(values.iter().map(|v| v.as_str()), self.match_mode).map(Some),
+        };
+
+        Ok(ExcludeFilter {
+            genres: matcher(&self.exclude_genre)?,
+            moods: matcher(&self.exclude_mood)?,
+            artists: matcher(&self.exclude_artist)?,
+            words: matcher(&self.exclude_words)?,
+        })
+    }
+
+    fn query(&self, date_field: DateField) -> Result<Option<Query>, MicError> {
+        self.query.as_deref().map(|q| Query::parse(q, date_field)).transpose()
+    }
+
+    fn date_ranges(&self, date_field: DateField) -> Result<DateRangeFilter, MicError> {
+        let today = chrono::offset::Local::now().date_naive();
+        let mut filter = DateRangeFilter {
+            date_field,
+            years: self.years.as_deref().map(|r| parse_range(r, parse_number)).transpose()?,
+            decades: self.decades.as_deref().map(|r| parse_range(r, parse_number)).transpose()?,
+            anniversary: self.anniversary.as_deref().map(|p| parse_period(p, today)).transpose()?,
+            ..Default::default()
+        };
+
+        if let Some(dates) = &self.dates {
+            filter.dates.push(parse_range(dates, parse_date)?);
+        }
+
+        // A date is a period of a single day, a partial date of its month or year
+        let date_or_period = |input: &str| match PartialDate::parse(input) {
+            Ok(date) => Ok((date.first_day(), date.last_day())),
+            Err(_) => parse_period(input, today),
+        };
+
+        if let Some(since) = &self.since {
+            filter.dates.push((Bound::Included(date_or_period(since)?.0), Bound::Unbounded));
+        }
+
+        if let Some(until) = &self.until {
+            filter.dates.push((Bound::Unbounded, Bound::Included(date_or_period(until)?.1)));
+        }
+
+        Ok(filter)
+    }
+}
+
+/// Range between optional limits, None when neither is given
+fn between<T>(min: Option<T>, max: Option<T>) -> Option<Range<T>> {
+    match (min, max) {
+        (None, None) => None,
+        (min, max) => Some((min.map_or(Bound::Unbounded, Bound::Included), max.map_or(Bound::Unbounded, Bound::Included))),
+    }
+}
@@ -1,10 +1,9 @@
 use std::error::Error;
 
 use clap::Args;
-use prettytable::{format, row, Table};
 use serde_json::Value;
 
-use crate::utils::{data::string_to_vec, songs::load_song_tag};
+use crate::utils::{config::Config, data::string_to_vec, output::Report, songs::load_song_tag};
 
 #[derive(Args)]
 pub struct InfoArgs {
@@ -20,14 +19,14 @@ pub struct InfoArgs {
     name: String,
 }
 
-fn is_tag_excluded(tagname: String, excludes: &Vec<String>) -> bool {
+fn is_tag_excluded(tagname: String, excludes: &[String]) -> bool {
     excludes.contains(&tagname)
 }
 
-pub fn get_track_info(args: InfoArgs) -> Result<(), Box<dyn Error>> {
-    let song_tag = load_song_tag(&args.name);
+pub fn get_track_info(args: InfoArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let song_tag = load_song_tag(&args.name)?;
 
-    let headers = row!["Tag", "Value"];
+    let mut report = Report::new(&[("tag", "Tag"), ("value", "Value")]);
 
     let song: Value = serde_json::to_value(&song_tag).unwrap();
     let excludes: Vec<String> = match args.exclude {
@@ -42,38 +41,26 @@ pub fn get_track_info(args: InfoArgs) -> Result<(), Box<dyn Error>> {
             table_rows.push((key, value));
         }
 
-        let mut table = Table::new();
-        table.set_format(*format::consts::FORMAT_NO_LINESEP);
-        table.add_row(headers);
-
         for (key, value) in table_rows {
             if is_tag_excluded(key.to_string(), &excludes) {
                 continue;
             }
 
-            match value {
-                Value::Array(arr) => {
-                    if arr.len() > 0 && args.missing {
-                        continue;
-                    }
-                },
-                Value::String(s) => {
-                    if !s.is_empty() && args.missing {
-                        continue;
-                    }
-                },
-                Value::Number(n) => {
-                    if n.as_u64().unwrap() != 0 && args.missing {
-                        continue;
-                    }
-                },
-                _ => {}
+            let has_value = match value {
+                Value::Array(arr) => !arr.is_empty(),
+                Value::String(s) => !s.is_empty(),
+                Value::Number(n) => n.as_u64().unwrap() != 0,
+                _ => false,
+            };
+
+            if has_value && args.missing {
+                continue;
             }
 
-            table.add_row(row![key, value]);
+            report.row(vec![(*key).into(), value.clone()]);
         }
 
-        table.printstd();
+        report.print(config.output)?;
     }
 
     Ok(())
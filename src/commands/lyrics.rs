@@ -0,0 +1,226 @@
+use std::{collections::HashMap, error::Error};
+
+use clap::{Args, Subcommand};
+
+use crate::utils::{
+    config::Config,
+    data::array_truncate,
+    error::{report_skipped, MicError},
+    filters::Predicate,
+    lyrics::{LyricsStats, Scoring, Tokenizer, Vocabulary},
+    output::Report,
+    query::{Field, GroupKey},
+    songs::{get_songs, SongData},
+};
+
+use super::{count::group_keys, filter::FilterArgs};
+
+#[derive(Args)]
+pub struct LyricsArgs {
+    #[clap(subcommand)]
+    commands: LyricsCommands,
+}
+
+#[derive(Subcommand)]
+enum LyricsCommands {
+    /// Words that set a group apart from the rest of the library ex. what each artist sings about
+    Distinctive(DistinctiveArgs),
+
+    /// Vocabulary, line length, words per minute and repetition by group ex. the wordiest artist
+    Stats(StatsArgs),
+}
+
+pub fn lyrics_command(args: LyricsArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    match args.commands {
+        LyricsCommands::Distinctive(args) => lyrics_distinctive(args, config)?,
+        LyricsCommands::Stats(args) => lyrics_stats(args, config)?,
+    };
+
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct DistinctiveArgs {
+    /// Tag to group by, any --where field ex. artist, album, genre, decade
+    field: String,
+
+    /// Only show this group ex. "Green Day", compared with --match
+    #[clap(short = 'g', long = "group")]
+    group: Option<String>,
+
+    /// How words are scored against the rest of the library
+    #[clap(short = 's', long = "scoring", value_enum, default_value_t)]
+    scoring: Scoring,
+
+    /// Words shown per group
+    #[clap(short = 'l', long = "length", default_value_t = 10)]
+    length: usize,
+
+    /// Leave out words used fewer times in the group
+    #[clap(long = "min-count", default_value_t = 2)]
+    min_count: usize,
+
+    /// Score phrases of 2 or 3 words instead of single words
+    #[clap(short = 'n', long = "ngram", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=3))]
+    ngram: u8,
+
+    /// Score words by their stem so "loving" and "loved" count as "love"
+    #[clap(long = "stem")]
+    stem: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
+
+pub fn lyrics_distinctive(args: DistinctiveArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let field = Field::parse_group(&args.field)?;
+    let group = args.filter.text(args.group.as_deref())?;
+    let songs: Vec<SongData> = args.filter.predicate(config)?.filter(get_songs(config)?);
+
+    let tokenizer = Tokenizer::new(&config.lyrics, args.stem);
+    let mut vocabulary: Vocabulary<GroupKey> = Vocabulary::default();
+    let mut skipped: Vec<MicError> = vec![];
+
+    for song in songs.iter().filter(|song| !song.lyrics.is_empty()) {
+        let tokens = tokenizer.tokens(song, args.ngram as usize);
+
+        vocabulary.add(group_keys(field, song, config, &mut skipped), &tokens);
+    }
+
+    report_skipped(&skipped);
+
+    let mut keys: Vec<&GroupKey> = vocabulary
+        .groups()
+        .filter(|key| group.as_ref().is_none_or(|m| m.matches(&key.to_string())))
+        .collect();
+    keys.sort();
+
+    if keys.is_empty() && config.output.is_table() {
+        println!("No lyrics match");
+        return Ok(());
+    }
+
+    let title = field.title();
+    let mut report = Report::new(&[(field.name(), &title), ("word", "Word"), ("score", "Score"), ("uses", "Uses")]);
+
+    for key in keys {
+        for (word, score, count) in vocabulary.distinctive(key, args.scoring, args.min_count).into_iter().take(args.length) {
+            report.row(vec![key.clone().into(), word.into(), ((score * 1000.0).round() / 1000.0).into(), count.into()]);
+        }
+    }
+
+    report.print(config.output)?;
+
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Tag to group by, any --where field ex. artist, album, year
+    #[clap(short = 'b', long = "by", default_value = "artist")]
+    field: String,
+
+    /// Order groups by a statistic, highest first, or by their value
+    #[clap(short = 's', long = "sort", value_enum, default_value_t)]
+    sort: StatsOrder,
+
+    /// Reverse the order
+    #[clap(short = 'r', long = "reverse")]
+    reverse: bool,
+
+    /// Return on top numbers
+    #[clap(short = 'l', long = "length")]
+    length: Option<usize>,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum StatsOrder {
+    /// Alphabetical or numeric
+    Value,
+    Songs,
+    #[default]
+    Words,
+    Unique,
+    /// Type/token ratio
+    Richness,
+    LineLength,
+    Wpm,
+    Repetition,
+}
+
+pub fn lyrics_stats(args: StatsArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let field = Field::parse_group(&args.field)?;
+    let songs: Vec<SongData> = args.filter.predicate(config)?.filter(get_songs(config)?);
+
+    let mut groups: HashMap<GroupKey, LyricsStats> = HashMap::new();
+    let mut skipped: Vec<MicError> = vec![];
+
+    for song in songs.iter().filter(|song| !song.lyrics.is_empty()) {
+        for key in group_keys(field, song, config, &mut skipped) {
+            groups.entry(key).or_default().add(song);
+        }
+    }
+
+    report_skipped(&skipped);
+
+    if groups.is_empty() && config.output.is_table() {
+        println!("No lyrics match");
+        return Ok(());
+    }
+
+    let mut groups: Vec<(GroupKey, LyricsStats)> = groups.into_iter().collect();
+
+    groups.sort_by(|(a_key, a), (b_key, b)| {
+        let order = match args.sort {
+            StatsOrder::Value => return a_key.cmp(b_key),
+            StatsOrder::Songs => b.songs.cmp(&a.songs),
+            StatsOrder::Words => b.words.cmp(&a.words),
+            StatsOrder::Unique => b.unique_words().cmp(&a.unique_words()),
+            StatsOrder::Richness => b.type_token_ratio().total_cmp(&a.type_token_ratio()),
+            StatsOrder::LineLength => b.line_length().total_cmp(&a.line_length()),
+            StatsOrder::Wpm => b.words_per_minute().total_cmp(&a.words_per_minute()),
+            StatsOrder::Repetition => b.repetition().total_cmp(&a.repetition()),
+        };
+
+        order.then_with(|| a_key.cmp(b_key))
+    });
+
+    if args.reverse {
+        groups.reverse();
+    }
+
+    array_truncate(&mut groups, args.length);
+
+    let title = field.title();
+    let mut report = Report::new(&[
+        (field.name(), &title),
+        ("songs", "Songs"),
+        ("words", "Words"),
+        ("unique", "Unique"),
+        ("type_token", "Type/Token"),
+        ("line_length", "Words/Line"),
+        ("wpm", "Words/Min"),
+        ("repetition", "Repeated Lines"),
+    ]);
+    let round = |value: f64, places: i32| (value * 10f64.powi(places)).round() / 10f64.powi(places);
+
+    for (key, stats) in groups {
+        report.row(vec![
+            key.into(),
+            stats.songs.into(),
+            stats.words.into(),
+            stats.unique_words().into(),
+            round(stats.type_token_ratio(), 3).into(),
+            round(stats.line_length(), 1).into(),
+            round(stats.words_per_minute(), 1).into(),
+            round(stats.repetition(), 3).into(),
+        ]);
+    }
+
+    report.print(config.output)?;
+
+    Ok(())
+}
@@ -1,6 +1,10 @@
 pub mod accg;
+pub mod cache;
 pub mod count;
+pub mod filter;
 pub mod info;
+pub mod lyrics;
 pub mod playlist;
+pub mod presets;
 pub mod time;
 pub mod wtp;
@@ -1,14 +1,18 @@
-use std::error::Error;
+use std::{error::Error, path::Path};
 
 use clap::Args;
 use rand::seq::SliceRandom;
 
 use crate::utils::{
+    config::Config,
     data::array_truncate,
-    date::parse_string_to_yearless_date,
+    filters::Predicate,
+    output::Report,
     songs::{get_songs, SongData, SongDataFilter},
 };
 
+use super::filter::FilterArgs;
+
 #[derive(Args)]
 pub struct PlaylistArgs {
     /// Album Title ex. "Dookie"
@@ -55,7 +59,7 @@ pub struct PlaylistArgs {
     #[clap(short = 'w', long = "week")]
     week: bool,
 
-    /// Get list by current week
+    /// Lyrics containing any of these words or lines
     #[clap(short = 'W', long = "words")]
     words: Option<String>,
 
@@ -67,25 +71,43 @@ pub struct PlaylistArgs {
     #[clap(short = 'r', long = "random")]
     random: bool,
 
+    #[clap(flatten)]
+    filter: FilterArgs,
+
     /// Filename for the playlist output without extension
     name: Option<String>,
 }
 
-pub fn generate_playlist(args: PlaylistArgs) -> Result<(), Box<dyn Error>> {
-    let songs: Vec<SongData> = get_songs()?;
-    let filter: SongDataFilter = SongDataFilter {
+/// Writes the song path relative to the configured playlist base
+fn playlist_entry(config: &Config, filename: &str) -> String {
+    let path = Path::new(filename);
+
+    match config.playlist_base(filename).and_then(|base| path.strip_prefix(base).ok()) {
+        Some(relative) => relative.to_string_lossy().to_string(),
+        None => filename.to_string(),
+    }
+}
+
+pub fn generate_playlist(args: PlaylistArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let filter = SongDataFilter {
+        day: args.day.map(u32::from),
         month: args.month,
         year: args.year,
-        genre: args.genre,
-        moods: args.moods,
-        artist: args.artist,
+        album: args.filter.text(args.album.as_deref())?,
+        genre: args.filter.list(args.genre.as_deref())?,
+        moods: args.filter.list(args.moods.as_deref())?,
+        artist: args.filter.text(args.artist.as_deref())?,
         decade: args.decade,
         week: args.week,
-        words: args.words,
+        words: args.filter.list(args.words.as_deref())?,
         instrumental: args.instrumental,
-        track: args.track,
+        track: args.filter.text(args.track.as_deref())?,
+        date_field: config.library.date_field,
         ..Default::default()
-    };
+    }
+    .and(args.filter.predicate(config)?);
+
+    let songs: Vec<SongData> = get_songs(config)?;
 
     let mut filtered_songs: Vec<_> = filter.filter(songs);
 
@@ -93,19 +115,33 @@ pub fn generate_playlist(args: PlaylistArgs) -> Result<(), Box<dyn Error>> {
         let mut rnd = rand::thread_rng();
         filtered_songs.shuffle(&mut rnd);
     } else {
+        let date_field = config.library.date_field;
+
         filtered_songs.sort_by(|a, b| {
-            let a_yearless = parse_string_to_yearless_date(&a.recording_date);
-            let b_yearless = parse_string_to_yearless_date(&b.recording_date);
+            let a_yearless = a.date(date_field).ok().flatten().map(|date| date.yearless());
+            let b_yearless = b.date(date_field).ok().flatten().map(|date| date.yearless());
             a_yearless.cmp(&b_yearless).then(a.filename.cmp(&b.filename))
         });
     }
 
-    let mut song_files: Vec<String> = filtered_songs
+    array_truncate(&mut filtered_songs, args.length);
+
+    let song_files: Vec<String> = filtered_songs
         .iter()
-        .map(|s| s.filename.replace("/home/andrew/music/", "").to_string())
+        .map(|s| playlist_entry(config, &s.filename))
         .collect();
 
-    array_truncate::<String>(&mut song_files, args.length);
+    // The .m3u file is always a plain list, --output only changes what's printed
+    if args.name.is_none() && !config.output.is_table() {
+        let mut report = Report::new(&[("path", "Path"), ("artist", "Artist"), ("album", "Album"), ("title", "Title")]);
+
+        for (path, song) in song_files.into_iter().zip(filtered_songs) {
+            report.row(vec![path.into(), song.track_artist.into(), song.album_title.into(), song.track_title.into()]);
+        }
+
+        report.print(config.output)?;
+        return Ok(());
+    }
 
     match args.name {
         Some(filename) => {
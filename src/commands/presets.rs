@@ -0,0 +1,168 @@
+use std::{collections::HashSet, error::Error, ffi::OsString, path::PathBuf};
+
+use clap::{Args, Subcommand};
+use crate::utils::{config::Config, output::Report};
+
+#[derive(Args)]
+pub struct PresetsArgs {
+    #[clap(subcommand)]
+    commands: PresetsCommands,
+}
+
+#[derive(Subcommand)]
+enum PresetsCommands {
+    /// Show the presets defined in the config
+    List,
+}
+
+pub fn presets_command(args: PresetsArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    match args.commands {
+        PresetsCommands::List => presets_list(config)?,
+    };
+
+    Ok(())
+}
+
+fn presets_list(config: &Config) -> Result<(), Box<dyn Error>> {
+    if config.presets.is_empty() && config.output.is_table() {
+        println!("No presets defined, add a [presets.<name>] section to the config");
+        return Ok(());
+    }
+
+    let mut report = Report::new(&[("name", "Name"), ("description", "Description"), ("args", "Arguments")]);
+
+    for (name, preset) in &config.presets {
+        let args: Vec<String> = preset.args.iter().map(|arg| quote(arg)).collect();
+
+        report.row(vec![format!("@{}", name).into(), preset.description.as_str().into(), args.join(" ").into()]);
+    }
+
+    report.print(config.output)?;
+
+    Ok(())
+}
+
+fn quote(arg: &str) -> String {
+    match arg.is_empty() || arg.contains(char::is_whitespace) {
+        true => format!("\"{}\"", arg),
+        false => arg.to_string(),
+    }
+}
+
+/// Loads the config and expands `@name` arguments, the config is only read when a preset is used
+pub fn expand_presets(args: Vec<OsString>, cli: &clap::Command) -> Result<Vec<OsString>, Box<dyn Error>> {
+    if !args.iter().skip(1).any(|arg| arg.to_string_lossy().starts_with('@')) {
+        return Ok(args);
+    }
+
+    let mut options = ValueOptions::new(cli);
+
+    Ok(Config::load(config_flag(&args).as_deref())?.expand_presets(args, |arg| options.takes_value(arg))?)
+}
+
+/// Options that expect a value, following the subcommands named in the arguments so a flag is looked up
+/// in the subcommand it's given to
+struct ValueOptions<'a> {
+    command: &'a clap::Command,
+    globals: HashSet<String>,
+}
+
+impl<'a> ValueOptions<'a> {
+    fn new(command: &'a clap::Command) -> ValueOptions<'a> {
+        ValueOptions { command, globals: HashSet::new() }
+    }
+
+    /// Called with every argument that isn't an option's value, in order
+    fn takes_value(&mut self, arg: &str) -> bool {
+        if let Some(subcommand) = self.command.find_subcommand(arg) {
+            // Global options of the parents can still be given after a subcommand
+            self.globals.extend(value_options(self.command, |option| option.is_global_set()));
+            self.command = subcommand;
+            return false;
+        }
+
+        self.globals.contains(arg) || value_options(self.command, |_| true).contains(arg)
+    }
+}
+
+/// Long and short options of a command that expect a value
+fn value_options(command: &clap::Command, keep: impl Fn(&clap::Arg) -> bool) -> HashSet<String> {
+    command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && arg.get_action().takes_values() && keep(arg))
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{}", long));
+            let short = arg.get_short().map(|short| format!("-{}", short));
+
+            long.into_iter().chain(short)
+        })
+        .collect()
+}
+
+/// Value of --config, read before the arguments are parsed
+fn config_flag(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().take_while(|arg| *arg != "--");
+
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::{config_flag, ValueOptions};
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn reads_the_config_flag() {
+        assert_eq!(config_flag(&args(&["mic", "--config", "a.toml", "count"])), Some(PathBuf::from("a.toml")));
+        assert_eq!(config_flag(&args(&["mic", "count", "--config=b.toml"])), Some(PathBuf::from("b.toml")));
+        assert_eq!(config_flag(&args(&["mic", "count", "--", "--config", "c.toml"])), None);
+    }
+
+    #[test]
+    fn finds_options_taking_a_value() {
+        use clap::{Arg, ArgAction, Command};
+
+        let cli = Command::new("mic").arg(Arg::new("config").long("config").global(true)).subcommand(
+            Command::new("playlist")
+                .arg(Arg::new("artist").short('A').long("artist"))
+                .arg(Arg::new("random").long("random").action(ArgAction::SetTrue))
+                .arg(Arg::new("name")),
+        );
+        let mut options = ValueOptions::new(&cli);
+
+        assert!(options.takes_value("--config") && !options.takes_value("-A"));
+        assert!(!options.takes_value("playlist"));
+        assert!(options.takes_value("-A") && options.takes_value("--artist") && options.takes_value("--config"));
+        assert!(!options.takes_value("--random") && !options.takes_value("name"));
+    }
+
+    #[test]
+    fn looks_options_up_in_the_given_subcommand() {
+        use clap::{Arg, ArgAction, Command};
+
+        let cli = Command::new("mic")
+            .subcommand(Command::new("count").arg(Arg::new("album").short('a').action(ArgAction::SetTrue)))
+            .subcommand(Command::new("playlist").arg(Arg::new("artist").short('a')));
+
+        let mut count = ValueOptions::new(&cli);
+        assert!(!count.takes_value("count") && !count.takes_value("-a"));
+
+        let mut playlist = ValueOptions::new(&cli);
+        assert!(!playlist.takes_value("playlist") && playlist.takes_value("-a"));
+    }
+}
@@ -1,8 +1,17 @@
 use clap::Args;
-use prettytable::{format, row, Table};
-use std::{collections::HashMap, error::Error};
+use std::error::Error;
 
-use crate::utils::{data::convert_sec_to_fmt_time, songs::{get_songs, SongDataFilter}};
+use crate::utils::{
+    albums::group_albums,
+    chart::{bar_chart, bucket_size, histogram},
+    config::Config,
+    data::convert_sec_to_fmt_time,
+    filters::Predicate,
+    output::Report,
+    songs::{get_songs, SongDataFilter},
+};
+
+use super::filter::FilterArgs;
 
 #[derive(Args)]
 pub struct TimeArgs {
@@ -18,62 +27,82 @@ pub struct TimeArgs {
     artist: Option<String>,
     #[clap(short = 'a', long = "album")]
     album: Option<String>,
-}
+    /// Also draw a histogram of track lengths
+    #[clap(short = 'c', long = "chart")]
+    chart: bool,
 
-pub fn times_of_music(args: TimeArgs) -> Result<(), Box<dyn Error>> {
-    let songs = get_songs()?;
-    let total_song_length;
-    let max_song;
-    let min_song;
-    let mut album_lengths: HashMap<String, u64> = HashMap::new();
-    let max_album;
-    let min_album;
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
 
-    let filter: SongDataFilter = SongDataFilter {
+pub fn times_of_music(args: TimeArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let filter = SongDataFilter {
         month: args.month,
         year: args.year,
-        genre: args.genre,
-        artist: args.artist,
+        decade: args.decade,
+        genre: args.filter.list(args.genre.as_deref())?,
+        artist: args.filter.text(args.artist.as_deref())?,
+        album: args.filter.text(args.album.as_deref())?,
+        date_field: config.library.date_field,
         ..Default::default()
-    };
+    }
+    .and(args.filter.predicate(config)?);
+
+    let songs = get_songs(config)?;
 
     let filtered_songs: Vec<_> = filter.filter(songs);
-    let track_times: Vec<u64> = filtered_songs
-        .iter()
-        .map(|s| {
-            let length = album_lengths.entry(s.album_title.clone()).or_insert(0);
-            *length += s.track_length;
-
-            s.track_length
-        })
-        .collect();
-    let hash_values = album_lengths.values().cloned();
-
-    min_song = track_times.iter().min().unwrap();
-    max_song = track_times.iter().max().unwrap();
-    total_song_length = track_times.iter().sum();
-
-    min_album = hash_values.clone().min().unwrap();
-    max_album = hash_values.clone().max().unwrap();
-    let mut table = Table::new();
-    table.set_format(*format::consts::FORMAT_NO_LINESEP);
-    table.add_row(row!["Name", "Times"]);
-
-    table.add_row(row!["Shortest album", convert_sec_to_fmt_time(min_album)]);
-    table.add_row(row![
-        "Avg album length",
-        convert_sec_to_fmt_time(total_song_length / (hash_values.len() as u64))
-    ]);
-    table.add_row(row!["Longest album", convert_sec_to_fmt_time(max_album)]);
-    table.add_row(row!["Shortest song", convert_sec_to_fmt_time(*min_song)]);
-    table.add_row(row![
-        "Avg song length",
-        convert_sec_to_fmt_time(total_song_length / (filtered_songs.len() as u64))
-    ]);
-    table.add_row(row!["Longest song", convert_sec_to_fmt_time(*max_song)]);
-    table.add_row(row!["Total song length", convert_sec_to_fmt_time(total_song_length)]);
-
-    table.printstd();
+
+    if filtered_songs.is_empty() && config.output.is_table() {
+        println!("No songs match");
+        return Ok(());
+    }
+    let mut report = Report::new(&[("name", "Name"), ("seconds", "Times")]);
+
+    if filtered_songs.is_empty() {
+        report.print(config.output)?;
+        return Ok(());
+    }
+
+    let track_times: Vec<u64> = filtered_songs.iter().map(|s| s.track_length).collect();
+    let album_lengths: Vec<u64> = group_albums(filtered_songs.clone()).iter().map(|a| a.runtime()).collect();
+
+    let min_song = track_times.iter().min().unwrap();
+    let max_song = track_times.iter().max().unwrap();
+    let total_song_length: u64 = track_times.iter().sum();
+
+    let min_album = *album_lengths.iter().min().unwrap();
+    let max_album = *album_lengths.iter().max().unwrap();
+    for (name, seconds) in [
+        ("Shortest album", min_album),
+        ("Avg album length", total_song_length / (album_lengths.len() as u64)),
+        ("Longest album", max_album),
+        ("Shortest song", *min_song),
+        ("Avg song length", total_song_length / (filtered_songs.len() as u64)),
+        ("Longest song", *max_song),
+        ("Total song length", total_song_length),
+    ] {
+        // Seconds stay numbers outside of tables so they can be summed and sorted
+        let time = match config.output.is_table() {
+            true => convert_sec_to_fmt_time(seconds).into(),
+            false => seconds.into(),
+        };
+
+        report.row(vec![name.into(), time]);
+    }
+
+    report.print(config.output)?;
+
+    if args.chart && config.output.is_table() {
+        // Half minutes up to half hours, whichever keeps the chart under 20 lines
+        let size = bucket_size(&track_times, &[30, 60, 120, 300, 600, 1800], 20);
+        let buckets: Vec<(String, usize)> = histogram(&track_times, size)
+            .into_iter()
+            .map(|(start, count)| (convert_sec_to_fmt_time(start), count))
+            .collect();
+
+        println!("\nTrack lengths");
+        println!("{}", bar_chart(&buckets));
+    }
 
     Ok(())
 }
@@ -3,10 +3,15 @@ use std::error::Error;
 use clap::Args;
 
 use crate::utils::{
-    date::parse_string_to_yearless_date,
-    songs::{get_albums, SongData, SongDataFilter},
+    config::Config,
+    albums::{get_albums, Album},
+    filters::Predicate,
+    output::Report,
+    songs::SongDataFilter,
 };
 
+use super::filter::FilterArgs;
+
 #[derive(Args)]
 pub struct WtpArgs {
     /// Day of release
@@ -32,28 +37,50 @@ pub struct WtpArgs {
     /// Get only what's left from the month, week or year
     #[clap(short = 'l', long = "left")]
     left: bool,
+
+    #[clap(flatten)]
+    filter: FilterArgs,
 }
 
-pub fn wtpn(args: WtpArgs) -> Result<(), Box<dyn Error>> {
-    let songs: Vec<SongData> = get_albums()?;
-    let filter: SongDataFilter = SongDataFilter {
+pub fn wtpn(args: WtpArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let filter = SongDataFilter {
+        day: args.day.map(u32::from),
         month: args.month,
         year: args.year,
+        decade: args.decade,
         week: args.week,
         left: args.left,
+        date_field: config.library.date_field,
         ..Default::default()
-    };
+    }
+    .and(args.filter.predicate(config)?);
+
+    let albums: Vec<Album> = get_albums(config)?;
+
+    let mut filtered_albums: Vec<Album> = filter.filter(albums);
 
-    let mut filtered_songs: Vec<_> = filter.filter(songs);
+    let date_field = config.library.date_field;
 
-    filtered_songs.sort_by(|a, b| {
-        let a_yearless = parse_string_to_yearless_date(&a.recording_date);
-        let b_yearless = parse_string_to_yearless_date(&b.recording_date);
-        a_yearless.cmp(&b_yearless)
-    });
+    filtered_albums.sort_by_key(|album| album.date(date_field).map(|date| date.yearless()));
+
+    let mut report = Report::new(&[("date", "Date"), ("artist", "Artist"), ("album", "Album"), ("discs", "Discs")]);
+
+    for album in filtered_albums {
+        let date = album.date(date_field).map(|date| date.to_string()).unwrap_or_default();
+        let discs = album.discs().len();
+
+        if config.output.is_table() {
+            match discs {
+                1 => println!("{} {} - {}", date, album.artist, album.title),
+                discs => println!("{} {} - {} ({} discs)", date, album.artist, album.title, discs),
+            }
+        }
+
+        report.row(vec![date.into(), album.artist.into(), album.title.into(), discs.into()]);
+    }
 
-    for song in filtered_songs {
-        println!("{} {} - {}", song.recording_date, song.track_artist, song.album_title);
+    if !config.output.is_table() {
+        report.print(config.output)?;
     }
 
     Ok(())
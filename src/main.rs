@@ -14,7 +14,9 @@
 *
 */
 
-use clap::{Parser, Subcommand};
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use clap::{CommandFactory, Parser, Subcommand};
 use commands::info::InfoArgs;
 use commands::info::get_track_info;
 
@@ -22,22 +24,45 @@ mod commands;
 mod utils;
 
 use crate::commands::accg::*;
+use crate::commands::cache::*;
 use crate::commands::count::*;
+use crate::commands::lyrics::*;
 use crate::commands::playlist::*;
+use crate::commands::presets::*;
 use crate::commands::time::*;
 use crate::commands::wtp::*;
+use crate::utils::{config::Config, date::DateField, error::MicError, output::OutputFormat};
 
 #[derive(Parser)]
 #[command(
     name = "mic",
     author = "andrewgxyz",
-    about = "A general tool around manipulating local music collection."
+    about = "A general tool around manipulating local music collection.",
+    after_help = "Exit codes: 0 ok, 1 error, 2 usage, 3 io, 4 tags, 5 date, 6 image, 7 cache, 8 config"
 )]
 struct Cli {
+    /// Path to the config file [default: ~/.config/mic/config.toml]
+    #[clap(long = "config", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Music library root, overrides the config (repeat for several roots)
+    #[clap(long = "library", global = true)]
+    pub library: Vec<PathBuf>,
+
+    /// Date tag used for filtering and sorting, overrides the config
+    #[clap(long = "date-field", global = true, value_enum)]
+    pub date_field: Option<DateField>,
+
+    /// Print results as a table or in a machine readable format
+    #[clap(long = "output", global = true, value_enum, default_value_t)]
+    pub output: OutputFormat,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
+// Parsed once per run, boxing the filter flags of every variant wouldn't buy anything
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 #[command(arg_required_else_help = true)]
 enum Command {
@@ -59,18 +84,47 @@ enum Command {
 
     /// Output tag information from filename
     Info(InfoArgs),
+
+    /// Analyse the words of lyrics
+    Lyrics(LyricsArgs),
+
+    /// Inspect and manage the song and cover caches
+    Cache(CacheArgs),
+
+    /// Saved filters from the config, used as @name in other commands
+    Presets(PresetsArgs),
+}
+
+fn main() -> ExitCode {
+    match expand_presets(std::env::args_os().collect(), &Cli::command()).and_then(|args| run(Cli::parse_from(args))) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+
+            match e.downcast_ref::<MicError>() {
+                Some(error) => ExitCode::from(error.exit_code()),
+                None => ExitCode::FAILURE,
+            }
+        },
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Cli::parse();
+fn run(args: Cli) -> Result<(), Box<dyn Error>> {
+    let config = Config::load(args.config.as_deref())?
+        .with_library(args.library)
+        .with_date_field(args.date_field)
+        .with_output(args.output);
 
     match args.command {
-        Command::Count(args) => count_music(args)?,
-        Command::Playlist(args) => generate_playlist(args)?,
-        Command::Time(args) => times_of_music(args)?,
-        Command::Wtp(args) => wtpn(args)?,
-        Command::Collage(args) => accg(args)?,
-        Command::Info(args) => get_track_info(args)?,
+        Command::Count(args) => count_music(args, &config)?,
+        Command::Playlist(args) => generate_playlist(args, &config)?,
+        Command::Time(args) => times_of_music(args, &config)?,
+        Command::Wtp(args) => wtpn(args, &config)?,
+        Command::Collage(args) => accg(args, &config)?,
+        Command::Info(args) => get_track_info(args, &config)?,
+        Command::Lyrics(args) => lyrics_command(args, &config)?,
+        Command::Cache(args) => cache_command(args, &config)?,
+        Command::Presets(args) => presets_command(args, &config)?,
     };
 
     Ok(())
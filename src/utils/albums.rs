@@ -0,0 +1,251 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use regex::Regex;
+
+use super::{
+    config::Config,
+    date::{DateField, PartialDate},
+    filters::HasSongData,
+    songs::{get_songs, SongData},
+    walker::walk_covers,
+};
+
+/// Songs sharing an album artist, album title and directory
+#[derive(Debug, Clone)]
+pub struct Album {
+    pub artist: String,
+    pub title: String,
+    /// Directory of the album, disc subdirectories like "cd1" are folded into their parent
+    pub dir: String,
+    /// Tracks ordered by disc and track number
+    pub tracks: Vec<SongData>,
+    pub cover: Option<PathBuf>,
+}
+
+impl Album {
+    /// First track of the album, album level tags are read from it
+    pub fn head(&self) -> &SongData {
+        &self.tracks[0]
+    }
+
+    /// Total length of every track in seconds
+    pub fn runtime(&self) -> u64 {
+        self.tracks.iter().map(|t| t.track_length).sum()
+    }
+
+    pub fn discs(&self) -> Vec<u32> {
+        let discs: BTreeSet<u32> = self.tracks.iter().map(disc_of).collect();
+
+        discs.into_iter().collect()
+    }
+
+    /// Distinct dates of the tracks, earliest first, tracks without a readable date are left out
+    pub fn dates(&self, field: DateField) -> Vec<PartialDate> {
+        let dates: BTreeSet<PartialDate> = self.tracks.iter().filter_map(|t| t.date(field).ok().flatten()).collect();
+
+        dates.into_iter().collect()
+    }
+
+    /// Earliest date of the album
+    pub fn date(&self, field: DateField) -> Option<PartialDate> {
+        self.dates(field).first().copied()
+    }
+
+    /// The album's own directory followed by the directory of each track, ex. its disc directories
+    pub fn dirs(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.dir.as_str()).chain(self.tracks.iter().map(|t| t.dir.as_str()))
+    }
+}
+
+/// Albums are filtered by their first track
+impl HasSongData for Album {
+    fn song_data(&self) -> &SongData {
+        self.head()
+    }
+}
+
+pub fn get_albums(config: &Config) -> Result<Vec<Album>, Box<dyn Error>> {
+    let mut albums = group_albums(get_songs(config)?);
+    let mut covers: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+    // The first cover found in a directory wins
+    for cover in walk_covers(config) {
+        let dir = cover.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        covers.entry(dir).or_insert(cover);
+    }
+
+    for album in albums.iter_mut() {
+        let cover = album.dirs().find_map(|dir| covers.get(Path::new(dir))).cloned();
+
+        album.cover = cover;
+    }
+
+    Ok(albums)
+}
+
+/// Groups songs into albums, ordered by artist, title and directory
+pub fn group_albums(songs: Vec<SongData>) -> Vec<Album> {
+    let mut groups: HashMap<(String, String, String), Vec<SongData>> = HashMap::new();
+
+    for song in songs {
+        let artist = match song.album_artist.is_empty() {
+            true => song.track_artist.clone(),
+            false => song.album_artist.clone(),
+        };
+        let key = (artist, song.album_title.clone(), album_dir(&song.dir));
+
+        groups.entry(key).or_default().push(song);
+    }
+
+    let mut albums: Vec<Album> = groups
+        .into_iter()
+        .map(|((artist, title, dir), mut tracks)| {
+            tracks.sort_by(|a, b| {
+                (disc_of(a), track_of(a), &a.filename).cmp(&(disc_of(b), track_of(b), &b.filename))
+            });
+
+            Album { artist, title, dir, tracks, cover: None }
+        })
+        .collect();
+
+    albums.sort_by(|a, b| (&a.artist, &a.title, &a.dir).cmp(&(&b.artist, &b.title, &b.dir)));
+
+    albums
+}
+
+fn disc_dir_regex() -> &'static Regex {
+    static DISC_DIR: OnceLock<Regex> = OnceLock::new();
+
+    DISC_DIR.get_or_init(|| Regex::new(r"(?i)^(cd|disc|disk)[\s_-]*(\d+)$").unwrap())
+}
+
+/// Track numbers like "3", "3/12", "1-03" with the disc first, or "B2" for the second track of a vinyl side
+fn track_regex() -> &'static Regex {
+    static TRACK: OnceLock<Regex> = OnceLock::new();
+
+    TRACK.get_or_init(|| Regex::new(r"^\s*(?:(\d+)-|([A-Za-z])\s*)?(\d+)").unwrap())
+}
+
+/// Side and number of a track within its disc, sides "A" and "B" order vinyl rips
+fn track_of(song: &SongData) -> (u32, Option<u32>) {
+    match track_regex().captures(&song.track_number) {
+        Some(c) => {
+            let side = c.get(2).map_or(0, |side| side.as_str().to_ascii_uppercase().as_bytes()[0] - b'A' + 1);
+
+            (side as u32, c[3].parse().ok())
+        },
+        None => (0, None),
+    }
+}
+
+/// Disc number of a song from its tag, a "1-03" track number or a "cd2" style directory, defaults to 1
+fn disc_of(song: &SongData) -> u32 {
+    if let Some(disc) = leading_number(&song.disc_number) {
+        return disc;
+    }
+
+    if let Some(disc) = track_regex().captures(&song.track_number).and_then(|c| c.get(1)?.as_str().parse().ok()) {
+        return disc;
+    }
+
+    let name = Path::new(&song.dir).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+
+    disc_dir_regex()
+        .captures(&name)
+        .and_then(|c| c[2].parse().ok())
+        .unwrap_or(1)
+}
+
+fn album_dir(dir: &str) -> String {
+    let path = Path::new(dir);
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+
+    match (disc_dir_regex().is_match(&name), path.parent()) {
+        (true, Some(parent)) => parent.display().to_string(),
+        _ => dir.to_string(),
+    }
+}
+
+/// Number at the start of a tag like "3/12" or "01"
+fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{album_dir, group_albums, leading_number};
+    use crate::utils::songs::SongData;
+
+    fn song(artist: &str, album: &str, dir: &str, track: &str) -> SongData {
+        SongData {
+            album_artist: artist.to_string(),
+            album_title: album.to_string(),
+            dir: dir.to_string(),
+            track_number: track.to_string(),
+            filename: format!("{}/{}", dir, track),
+            ..Default::default()
+        }
+    }
+
+    fn tracks(songs: Vec<SongData>) -> Vec<String> {
+        group_albums(songs)[0].tracks.iter().map(|t| t.track_number.clone()).collect()
+    }
+
+    #[test]
+    fn reads_leading_numbers() {
+        assert_eq!(leading_number("3/12"), Some(3));
+        assert_eq!(leading_number(" 07"), Some(7));
+        assert_eq!(leading_number("A1"), None);
+    }
+
+    #[test]
+    fn orders_disc_and_side_track_numbers() {
+        let dir = "/music/pixies/doolittle";
+        let songs = ["2-01", "1-10", "1-02"].map(|t| song("Pixies", "Doolittle", dir, t)).to_vec();
+
+        assert_eq!(tracks(songs.clone()), vec!["1-02", "1-10", "2-01"]);
+        assert_eq!(group_albums(songs)[0].discs(), vec![1, 2]);
+
+        let songs = ["B1", "A2", "A1"].map(|t| song("Pixies", "Doolittle", dir, t)).to_vec();
+
+        assert_eq!(tracks(songs), vec!["A1", "A2", "B1"]);
+    }
+
+    #[test]
+    fn folds_disc_directories_into_the_album() {
+        assert_eq!(album_dir("/music/bjork/post/CD1"), "/music/bjork/post");
+        assert_eq!(album_dir("/music/bjork/post/Disc 2"), "/music/bjork/post");
+        assert_eq!(album_dir("/music/bjork/post"), "/music/bjork/post");
+
+        let songs = vec![
+            song("Björk", "Post", "/music/bjork/post/Disc 2", "1"),
+            song("Björk", "Post", "/music/bjork/post/CD1", "2"),
+            song("Björk", "Post", "/music/bjork/post/CD1", "1"),
+        ];
+        let albums = group_albums(songs);
+
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].discs(), vec![1, 2]);
+        assert_eq!(albums[0].tracks.iter().map(|t| t.dir.as_str()).collect::<Vec<_>>()[2], "/music/bjork/post/Disc 2");
+    }
+
+    #[test]
+    fn keeps_artists_with_the_same_album_title_apart() {
+        let songs = vec![
+            song("Queen", "Greatest Hits", "/music/queen/greatest-hits", "1"),
+            song("ABBA", "Greatest Hits", "/music/abba/greatest-hits", "1"),
+            song("ABBA", "Greatest Hits", "/music/abba/greatest-hits", "2"),
+        ];
+        let albums = group_albums(songs);
+
+        assert_eq!(albums.iter().map(|a| (a.artist.as_str(), a.tracks.len())).collect::<Vec<_>>(), vec![("ABBA", 2), ("Queen", 1)]);
+    }
+}
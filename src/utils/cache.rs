@@ -1,56 +1,314 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error,
     fs::OpenOptions,
     io::{Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::from_str;
 
+use super::{error::MicError, store::Store};
+
+/// Bumped whenever the layout of the JSON export changes
+const CACHE_VERSION: u32 = 1;
+
+/// Anything kept in the cache database
+pub trait CacheRecord: Serialize + DeserializeOwned {
+    /// Table holding the records
+    const TABLE: &'static str;
+    /// JSON file used by older versions, imported once into the database
+    const JSON_NAME: &'static str;
+    /// Indexed columns of the table, bump the store's schema version when changing these
+    const COLUMNS: &'static [&'static str];
+
+    /// Values for `COLUMNS`, in the same order
+    fn indexed_columns(&self) -> Vec<String>;
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CacheData<T> {
-    pub data: HashMap<String, T>,
+    #[serde(default)]
+    pub version: u32,
+    pub data: HashMap<String, CacheEntry<T>>,
+    #[serde(skip)]
+    changed: HashSet<String>,
+    #[serde(skip)]
+    removed: HashSet<String>,
+    /// Stored entries that weren't decoded, see `Store::load_where`
+    #[serde(skip)]
+    unloaded: HashSet<String>,
+    /// Stored entries that failed to decode, their files are read again
+    #[serde(skip)]
+    pub unreadable: Vec<MicError>,
 }
 
-pub fn load_cache_file<T>(cache_name: &str) -> Result<CacheData<T>, Box<dyn Error>>
-where
-    T: DeserializeOwned,
-{
-    let cache_dir = dirs::cache_dir().unwrap();
+/// A cached value along with the state of the file it was read from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry<T> {
+    pub fingerprint: Fingerprint,
+    pub value: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Modified time in nanoseconds since the epoch
+    pub mtime: u64,
+    pub size: u64,
+    /// Blake3 hash of the contents, only set when hashing is enabled
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path, with_hash: bool) -> Result<Fingerprint, MicError> {
+        let metadata = std::fs::metadata(path).map_err(|e| MicError::io(path, e))?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let hash = match with_hash {
+            true => {
+                let contents = std::fs::read(path).map_err(|e| MicError::io(path, e))?;
+
+                Some(blake3::hash(&contents).to_hex().to_string())
+            },
+            false => None,
+        };
+
+        Ok(Fingerprint { mtime, size: metadata.len(), hash })
+    }
+
+    /// Whether a cached entry taken with this fingerprint is still valid for `current`
+    pub fn is_fresh(&self, current: &Fingerprint) -> bool {
+        let same_hash = match (&self.hash, &current.hash) {
+            (Some(cached), Some(current)) => cached == current,
+            (None, Some(_)) => false,
+            _ => true,
+        };
+
+        self.mtime == current.mtime && self.size == current.size && same_hash
+    }
+}
+
+impl<T> Default for CacheData<T> {
+    fn default() -> Self {
+        CacheData::new()
+    }
+}
+
+impl<T> CacheData<T> {
+    pub fn new() -> CacheData<T> {
+        CacheData {
+            version: CACHE_VERSION,
+            data: HashMap::new(),
+            changed: HashSet::new(),
+            removed: HashSet::new(),
+            unloaded: HashSet::new(),
+            unreadable: vec![],
+        }
+    }
+
+    /// No entry is stored, decoded or not
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.unloaded.is_empty()
+    }
+
+    /// Whether every stored entry was decoded, or replaced since
+    pub fn is_complete(&self) -> bool {
+        self.unloaded.is_empty()
+    }
+
+    pub fn add_unloaded(&mut self, filename: String) {
+        self.unloaded.insert(filename);
+    }
+
+    /// Cached value for a file, as long as the file hasn't changed since
+    pub fn get_fresh(&self, filename: &str, current: &Fingerprint) -> Option<&T> {
+        self.data
+            .get(filename)
+            .filter(|entry| entry.fingerprint.is_fresh(current))
+            .map(|entry| &entry.value)
+    }
+
+    pub fn insert(&mut self, filename: String, fingerprint: Fingerprint, value: T) {
+        self.removed.remove(&filename);
+        self.unloaded.remove(&filename);
+        self.changed.insert(filename.clone());
+        self.data.insert(filename, CacheEntry { fingerprint, value });
+    }
+
+    pub fn remove(&mut self, filename: &str) {
+        if self.data.remove(filename).is_some() | self.unloaded.remove(filename) {
+            self.changed.remove(filename);
+            self.removed.insert(filename.to_string());
+        }
+    }
+
+    /// Drops entries whose file no longer exists, returns how many were removed
+    pub fn prune_missing(&mut self) -> usize {
+        let missing: Vec<String> = self
+            .data
+            .keys()
+            .chain(self.unloaded.iter())
+            .filter(|filename| !Path::new(filename).exists())
+            .cloned()
+            .collect();
+
+        for filename in missing.iter() {
+            self.remove(filename);
+        }
+
+        missing.len()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.data.values().map(|entry| &entry.value)
+    }
+
+    /// Entries inserted or replaced since the cache was loaded
+    pub fn changed(&self) -> impl Iterator<Item = &String> {
+        self.changed.iter()
+    }
+
+    /// Entries removed since the cache was loaded
+    pub fn removed(&self) -> impl Iterator<Item = &String> {
+        self.removed.iter()
+    }
+
+    fn mark_all_changed(&mut self) {
+        self.changed = self.data.keys().cloned().collect();
+    }
+}
+
+/// Location of a file inside `~/.cache/mic`, creating the directory when needed
+pub fn cache_file_path(cache_name: &str) -> Result<PathBuf, MicError> {
+    let cache_dir = dirs::cache_dir().ok_or_else(|| MicError::cache("no cache directory available"))?;
     let app_cache = cache_dir.join("mic");
 
     if !app_cache.exists() {
-        std::fs::create_dir_all(&app_cache)?;
+        std::fs::create_dir_all(&app_cache).map_err(|e| MicError::io(&app_cache, e))?;
     }
 
-    let file_path = app_cache.join(cache_name);
+    Ok(app_cache.join(cache_name))
+}
+
+/// Loads a cache from the database, importing the JSON file of older versions the first time
+pub fn load_cache<T: CacheRecord>() -> Result<CacheData<T>, Box<dyn Error>> {
+    load_cache_where(|_, _| true)
+}
+
+/// Loads the entries `wanted` accepts, see `Store::load_where`
+pub fn load_cache_where<T, F>(wanted: F) -> Result<CacheData<T>, Box<dyn Error>>
+where
+    T: CacheRecord,
+    F: Fn(&str, &Fingerprint) -> bool,
+{
+    let mut store = Store::open()?;
+    let cache = store.load_where::<T, _>(&wanted)?;
+    let legacy = cache_file_path(T::JSON_NAME)?;
 
-    Ok(match file_path.exists() {
-        true => {
-            let cache_file = OpenOptions::new().read(true).open(file_path)?;
-            let reader = std::io::BufReader::new(cache_file);
+    if !cache.is_empty() || !legacy.exists() {
+        report_unreadable(&cache);
+        return Ok(cache);
+    }
 
-            let mut json_string = String::new();
-            reader.take(u64::MAX as u64).read_to_string(&mut json_string)?;
+    // An unreadable legacy file is simply left behind, the next scan fills the table
+    if let Ok(mut imported) = load_cache_file::<T>(&legacy) {
+        imported.mark_all_changed();
+        store.save(&imported)?;
+        eprintln!("Imported {} into the cache database", T::JSON_NAME);
+    }
 
-            from_str(&json_string)?
-        },
-        false => CacheData { data: HashMap::new() },
-    })
+    let backup = legacy.with_extension("json.bak");
+    std::fs::rename(&legacy, &backup).map_err(|e| MicError::io(&legacy, e))?;
+
+    let cache = store.load_where::<T, _>(&wanted)?;
+    report_unreadable(&cache);
+
+    Ok(cache)
 }
 
-pub fn save_cache_file<T>(data: &CacheData<T>, cache_name: &str) -> Result<(), Box<dyn Error>>
+fn report_unreadable<T>(cache: &CacheData<T>) {
+    if cache.unreadable.is_empty() {
+        return;
+    }
+
+    eprintln!("Ignored {} cache entries:", cache.unreadable.len());
+
+    for error in cache.unreadable.iter() {
+        eprintln!("  {}", error);
+    }
+}
+
+/// Writes the entries changed since `load_cache`
+pub fn save_cache<T: CacheRecord>(data: &CacheData<T>) -> Result<(), Box<dyn Error>> {
+    Store::open()?.save(data)?;
+
+    Ok(())
+}
+
+pub fn clear_cache<T: CacheRecord>() -> Result<(), Box<dyn Error>> {
+    Store::open()?.clear::<T>()?;
+
+    Ok(())
+}
+
+/// Copies a cache into a JSON file, returns the number of entries written
+pub fn export_cache<T: CacheRecord>(path: &Path) -> Result<usize, Box<dyn Error>> {
+    let cache = Store::open()?.load::<T>()?;
+
+    save_cache_file(&cache, path)?;
+
+    Ok(cache.data.len())
+}
+
+/// Merges a JSON export into the cache, returns the number of entries read
+pub fn import_cache<T: CacheRecord>(path: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut imported = load_cache_file::<T>(path)?;
+
+    imported.mark_all_changed();
+    Store::open()?.save(&imported)?;
+
+    Ok(imported.data.len())
+}
+
+/// Reads a cache from its JSON form
+pub fn load_cache_file<T>(file_path: &Path) -> Result<CacheData<T>, Box<dyn Error>>
+where
+    T: DeserializeOwned,
+{
+    let cache_file = OpenOptions::new().read(true).open(file_path).map_err(|e| MicError::io(file_path, e))?;
+    let reader = std::io::BufReader::new(cache_file);
+
+    let mut json_string = String::new();
+    reader.take(u64::MAX).read_to_string(&mut json_string).map_err(|e| MicError::io(file_path, e))?;
+
+    match from_str::<CacheData<T>>(&json_string) {
+        Ok(cache) if cache.version == CACHE_VERSION => Ok(cache),
+        Ok(_) => Err(MicError::cache(format!("{} was written by another version", file_path.display())).into()),
+        Err(e) => Err(MicError::cache(format!("{} is corrupt, {}", file_path.display(), e)).into()),
+    }
+}
+
+/// Writes a cache in its JSON form
+pub fn save_cache_file<T>(data: &CacheData<T>, file_path: &Path) -> Result<(), Box<dyn Error>>
 where
     T: Serialize,
 {
-    let serialized = serde_json::to_string(data)?;
-    let cache_dir = dirs::cache_dir().unwrap();
-    let app_cache = cache_dir.join("mic");
-    let file_path = app_cache.join(cache_name);
-    let mut cache_file = OpenOptions::new().create(true).write(true).open(file_path)?;
+    let serialized = serde_json::to_string(data).map_err(MicError::cache)?;
+    let mut cache_file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(file_path)
+        .map_err(|e| MicError::io(file_path, e))?;
 
-    cache_file.write_all(serialized.as_bytes())?;
+    cache_file.write_all(serialized.as_bytes()).map_err(|e| MicError::io(file_path, e))?;
 
     Ok(())
 }
@@ -0,0 +1,96 @@
+use console::{measure_text_width, pad_str, Alignment, Term};
+
+/// Width used when stdout isn't a terminal
+const DEFAULT_WIDTH: usize = 80;
+
+/// Eighths of a block, for bars that end between two cells
+const PARTIAL_BLOCKS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+/// Horizontal bars scaled to the terminal width, one line per row
+pub fn bar_chart(rows: &[(String, usize)]) -> String {
+    let width = Term::stdout().size_checked().map_or(DEFAULT_WIDTH, |(_, columns)| columns as usize);
+
+    bars(rows, width)
+}
+
+fn bars(rows: &[(String, usize)], width: usize) -> String {
+    let label_width = rows.iter().map(|(label, _)| measure_text_width(label)).max().unwrap_or(0);
+    let count_width = rows.iter().map(|(_, count)| count.to_string().len()).max().unwrap_or(0);
+    let max = rows.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1);
+
+    // Label, bar and count are separated by a space each
+    let bar_width = width.saturating_sub(label_width + count_width + 2).max(10);
+
+    rows.iter()
+        .map(|(label, count)| {
+            let eighths = count * bar_width * 8 / max;
+            let bar = "█".repeat(eighths / 8) + PARTIAL_BLOCKS[eighths % 8];
+
+            format!(
+                "{} {} {:>count_width$}",
+                pad_str(label, label_width, Alignment::Left, None),
+                pad_str(&bar, bar_width, Alignment::Left, None),
+                count,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Counts values into buckets of `size` from the smallest to the largest value, empty buckets included
+pub fn histogram(values: &[u64], size: u64) -> Vec<(u64, usize)> {
+    let size = size.max(1);
+    let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) else {
+        return vec![];
+    };
+
+    (min / size..=max / size)
+        .map(|bucket| (bucket * size, values.iter().filter(|v| *v / size == bucket).count()))
+        .collect()
+}
+
+/// Smallest of the given bucket sizes that keeps the histogram within `max_buckets`
+pub fn bucket_size(values: &[u64], sizes: &[u64], max_buckets: u64) -> u64 {
+    let spread = values.iter().max().unwrap_or(&0) - values.iter().min().unwrap_or(&0);
+
+    sizes.iter().copied().find(|size| spread / size < max_buckets).unwrap_or(*sizes.last().unwrap_or(&1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bars, bucket_size, histogram};
+
+    #[test]
+    fn scales_bars_to_the_largest_count() {
+        let rows = vec![("punk".to_string(), 4), ("metal".to_string(), 1), ("pop".to_string(), 0)];
+        let chart = bars(&rows, 21);
+        let lines: Vec<&str> = chart.lines().collect();
+
+        // 21 columns leave 13 for the bars, a quarter of 13 is 3 blocks and a quarter
+        assert_eq!(lines[0], format!("punk  {} 4", "█".repeat(13)));
+        assert_eq!(lines[1], format!("metal {}▎{} 1", "█".repeat(3), " ".repeat(9)));
+        assert_eq!(lines[2], format!("pop   {} 0", " ".repeat(13)));
+    }
+
+    #[test]
+    fn keeps_a_minimum_bar_width() {
+        let chart = bars(&[("a".to_string(), 1)], 0);
+
+        assert_eq!(chart, format!("a {} 1", "█".repeat(10)));
+    }
+
+    #[test]
+    fn counts_values_into_buckets() {
+        assert_eq!(histogram(&[65, 61, 190, 100], 60), vec![(60, 3), (120, 0), (180, 1)]);
+        assert_eq!(histogram(&[5], 0), vec![(5, 1)]);
+        assert!(histogram(&[], 60).is_empty());
+    }
+
+    #[test]
+    fn picks_the_smallest_bucket_size_that_fits() {
+        assert_eq!(bucket_size(&[100, 400], &[30, 60, 120], 20), 30);
+        assert_eq!(bucket_size(&[100, 1300], &[30, 60, 120], 20), 120);
+        assert_eq!(bucket_size(&[0, 100_000], &[30, 60, 120], 20), 120);
+        assert_eq!(bucket_size(&[], &[30, 60], 20), 30);
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{date::DateField, error::MicError, lyrics::Language, output::OutputFormat};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Settings read from `~/.config/mic/config.toml`, every section is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub library: LibraryConfig,
+    pub collage: CollageConfig,
+    pub playlist: PlaylistConfig,
+    pub cache: CacheConfig,
+    pub scan: ScanConfig,
+    pub lyrics: LyricsConfig,
+    /// Saved arguments used as `@name`, ex. `mic playlist @sunday-jazz`
+    pub presets: BTreeMap<String, Preset>,
+    /// Set by the --output flag
+    #[serde(skip)]
+    pub output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    pub description: String,
+    /// Arguments put in place of `@name`, filters as well as sort, limit and output flags
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LibraryConfig {
+    /// Directories holding the music collection
+    pub roots: Vec<PathBuf>,
+    /// Glob of album directories relative to a root, ex. "*/*" for artist/album
+    pub layout: String,
+    /// File extensions treated as audio
+    pub extensions: Vec<String>,
+    /// File names, without extension, treated as album covers
+    pub cover_names: Vec<String>,
+    /// Descend into symlinked directories while scanning
+    pub follow_symlinks: bool,
+    /// Gitignore style files listing paths to leave out of scans
+    pub ignore_files: Vec<String>,
+    /// Date tag used for filtering and sorting, "recording" or "release"
+    pub date_field: DateField,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CollageConfig {
+    /// Where generated collages are written when no filename is given
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PlaylistConfig {
+    /// Prefix stripped from every playlist entry, defaults to the library root
+    pub path_base: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Also compare a hash of each file's contents, slower but catches edits that keep mtime
+    pub hash: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// Threads used to read tags and decode covers, 0 uses one per core
+    pub threads: usize,
+    /// Show a progress bar once this many files need reading
+    pub progress_threshold: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LyricsConfig {
+    /// Stopword lists used for songs without a language tag
+    pub languages: Vec<Language>,
+    /// Extra stopwords per language, ex. `english = ["yeah", "oh"]`
+    pub stopwords: HashMap<Language, Vec<String>>,
+    /// Reduce words to their stem so "loving" and "loved" count as "love"
+    pub stem: bool,
+    /// Shorter words are dropped
+    pub min_length: usize,
+}
+
+impl Default for LyricsConfig {
+    fn default() -> Self {
+        LyricsConfig { languages: vec![Language::English], stopwords: HashMap::new(), stem: false, min_length: 2 }
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig { threads: 0, progress_threshold: 200 }
+    }
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        LibraryConfig {
+            roots: dirs::audio_dir().into_iter().collect(),
+            layout: "**".to_string(),
+            extensions: ["flac", "mp3", "wav", "opus", "ogg", "m4a"].map(String::from).to_vec(),
+            cover_names: vec!["cover".to_string()],
+            follow_symlinks: false,
+            ignore_files: vec![".micignore".to_string()],
+            date_field: DateField::default(),
+        }
+    }
+}
+
+impl Default for CollageConfig {
+    fn default() -> Self {
+        let pictures = dirs::picture_dir().unwrap_or_else(|| PathBuf::from("."));
+
+        CollageConfig { output_dir: pictures.join("accg") }
+    }
+}
+
+impl Config {
+    /// Reads the config file, falling back to defaults when the default file is missing
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+        let file_path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_config_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let contents = std::fs::read_to_string(&file_path).map_err(|e| MicError::io(&file_path, e))?;
+        let mut config: Config = toml::from_str(&contents)
+            .map_err(|e| MicError::config(format!("{}: {}", file_path.display(), e)))?;
+
+        config.library.roots = config.library.roots.iter().map(|r| expand_home(r)).collect();
+        config.collage.output_dir = expand_home(&config.collage.output_dir);
+        config.playlist.path_base = config.playlist.path_base.as_deref().map(expand_home);
+
+        Ok(config)
+    }
+
+    /// Replaces the configured library roots, used by the `--library` flag
+    pub fn with_library(mut self, roots: Vec<PathBuf>) -> Config {
+        if !roots.is_empty() {
+            self.library.roots = roots.iter().map(|r| expand_home(r)).collect();
+        }
+
+        self
+    }
+
+    /// Replaces the configured date tag, used by the `--date-field` flag
+    pub fn with_date_field(mut self, date_field: Option<DateField>) -> Config {
+        if let Some(date_field) = date_field {
+            self.library.date_field = date_field;
+        }
+
+        self
+    }
+
+    /// Replaces every `@name` argument with the arguments of its preset
+    ///
+    /// `takes_value` is called with the arguments in order and tells whether an option like "--artist" expects a
+    /// value, an `@name` right after one is that option's value and is left alone.
+    pub fn expand_presets(&self, args: Vec<OsString>, mut takes_value: impl FnMut(&str) -> bool) -> Result<Vec<OsString>, MicError> {
+        let mut expanded = vec![];
+        let mut positional = false;
+        let mut is_value = false;
+
+        for arg in args {
+            match arg.to_str().and_then(|a| a.strip_prefix('@')) {
+                Some(name) if !positional && !is_value => {
+                    let preset = self
+                        .presets
+                        .get(name)
+                        .ok_or_else(|| MicError::config(format!("no preset named \"{}\"", name)))?;
+
+                    expanded.extend(preset.args.iter().map(OsString::from));
+                    is_value = false;
+                },
+                _ => {
+                    // Everything after "--" is passed as is
+                    positional |= arg == "--";
+                    is_value = !is_value && arg.to_str().is_some_and(&mut takes_value);
+                    expanded.push(arg);
+                },
+            }
+        }
+
+        Ok(expanded)
+    }
+
+    pub fn with_output(mut self, output: OutputFormat) -> Config {
+        self.output = output;
+        self
+    }
+
+    /// Base directory a song's path is written relative to in playlists
+    pub fn playlist_base(&self, filename: &str) -> Option<&Path> {
+        match &self.playlist.path_base {
+            Some(base) => Some(base.as_path()),
+            None => self
+                .library
+                .roots
+                .iter()
+                .find(|root| Path::new(filename).starts_with(root))
+                .map(|root| root.as_path()),
+        }
+    }
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("mic").join(CONFIG_FILE_NAME))
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{Config, Preset};
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        let args = ["--genre", "punk", "--years", "1990..1999"].map(String::from).to_vec();
+
+        config.presets.insert("punk".to_string(), Preset { description: String::new(), args });
+        config
+    }
+
+    fn expand(args: &[&str]) -> Result<Vec<OsString>, String> {
+        let args = args.iter().map(OsString::from).collect();
+
+        config().expand_presets(args, |option| option == "-A").map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn expands_presets_in_place() {
+        assert_eq!(
+            expand(&["mic", "playlist", "@punk", "--random", "mix"]).unwrap(),
+            ["mic", "playlist", "--genre", "punk", "--years", "1990..1999", "--random", "mix"].map(OsString::from),
+        );
+    }
+
+    #[test]
+    fn leaves_option_values_and_positionals_alone() {
+        let args = ["mic", "playlist", "-A", "@ease", "--", "@punk"];
+
+        assert_eq!(expand(&args).unwrap(), args.map(OsString::from));
+    }
+
+    #[test]
+    fn rejects_unknown_presets() {
+        assert_eq!(expand(&["mic", "count", "@jazz"]).unwrap_err(), "config: no preset named \"jazz\"");
+    }
+}
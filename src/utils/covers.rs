@@ -1,24 +1,24 @@
 use std::{
-    collections::hash_map::Entry,
+    collections::{HashMap, HashSet},
     error::Error,
-    sync::{Arc, Mutex},
+    path::PathBuf,
 };
 
-use chrono::Datelike;
-use glob::{glob_with, MatchOptions};
-use image::{DynamicImage, GenericImageView};
+use image::{DynamicImage, GenericImageView, ImageFormat};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    cache::{load_cache_file, save_cache_file},
-    date::parse_string_to_datetime,
-    songs::{get_albums, SongData},
-    filters::{contains_list_of_strings, equals_same_value, match_decade, match_current_week, match_no_lyrics, match_lyrics_contain_words}, data::vec_u8_to_vec_point,
-    kmeans::k_means
+    cache::{cache_file_path, load_cache_where, save_cache, CacheRecord},
+    config::Config,
+    error::{report_skipped, MicError},
+    albums::{get_albums, Album},
+    songs::SongData,
+    filters::HasSongData,
+    data::vec_u8_to_vec_point,
+    kmeans::k_means,
+    scan::{fingerprint_files, is_unchanged, merge_results, pending_files, read_parallel},
 };
 
-use rayon::prelude::*;
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlbumCoverData {
     pub image: ImageCache,
@@ -30,146 +30,147 @@ pub struct AlbumCoverData {
 pub struct ImageCache {
     pub width: u32,
     pub height: u32,
-    pub pixels: Vec<u8>,
+    /// PNG thumbnail in the thumbnail directory, named after the hash of the source cover
+    pub thumbnail: PathBuf,
     pub dominant_colors: Vec<(u8, u8, u8)>
 }
 
-#[derive(Default)]
-pub struct AlbumCoverDataFilter {
-    pub day: Option<u32>,
-    pub month: Option<u32>,
-    pub year: Option<i32>,
-    pub artist: Option<String>,
-    pub album: Option<String>,
-    pub genre: Option<String>,
-    pub moods: Option<String>,
-    pub words: Option<String>,
-    pub decade: Option<u16>,
-    pub track: Option<String>,
-    pub week: bool,
-    pub instrumental: bool,
+impl HasSongData for AlbumCoverData {
+    fn song_data(&self) -> &SongData {
+        &self.album_data
+    }
 }
 
-impl AlbumCoverDataFilter {
-    pub fn filter(&self, covers: Vec<AlbumCoverData>) -> Vec<AlbumCoverData> {
-        covers.into_iter().filter(|cover| self.matches(cover)).collect()
+impl ImageCache {
+    /// Decodes a cover, writes its thumbnail and works out the palette
+    pub fn create(cover_name: &str) -> Result<ImageCache, MicError> {
+        let bytes = std::fs::read(cover_name).map_err(|e| MicError::io(cover_name, e))?;
+        let thumbnail = thumbnail_dir()?.join(format!("{}.png", blake3::hash(&bytes).to_hex()));
+        let img = image::load_from_memory(&bytes).map_err(|e| MicError::image(cover_name, e))?;
+
+        let resize = img.resize_exact(480, 480, image::imageops::FilterType::Triangle);
+        let (width, height) = resize.dimensions();
+        let pixels = resize.to_rgb8();
+
+        DynamicImage::ImageRgb8(pixels.clone())
+            .save_with_format(&thumbnail, ImageFormat::Png)
+            .map_err(|e| MicError::image(&thumbnail.to_string_lossy(), e))?;
+
+        let points = vec_u8_to_vec_point(pixels.into_raw(), width);
+        let clusters = k_means(points, 5, 10);
+        let dominant_colors: Vec<(u8,u8,u8)> = clusters.iter().map(|cluster| cluster.centroid.color).collect();
+
+        Ok(ImageCache { width, height, thumbnail, dominant_colors })
     }
 
-    pub fn matches(&self, cover: &AlbumCoverData) -> bool {
-        let dt = parse_string_to_datetime(&cover.album_data.recording_date)
-            .expect("Something went wrong with date parsing");
+    /// Reads the thumbnail back from disk
+    pub fn load(&self) -> Result<DynamicImage, MicError> {
+        image::open(&self.thumbnail).map_err(|e| MicError::image(&self.thumbnail.to_string_lossy(), e))
+    }
+}
 
-        let mut matches: Vec<bool> = vec![];
+const THUMBNAIL_DIR: &str = "thumbnails";
 
-        // Matching Genres & Moods
-        matches.push(contains_list_of_strings(&self.genre, &cover.album_data.genre));
-        matches.push(contains_list_of_strings(&self.moods, &cover.album_data.mood));
+/// Directory holding cover thumbnails, created when missing
+pub fn thumbnail_dir() -> Result<PathBuf, MicError> {
+    let dir = cache_file_path(THUMBNAIL_DIR)?;
 
-        // Matching Genres
-        matches.push(match_lyrics_contain_words(&self.words, &cover.album_data.lyrics));
+    std::fs::create_dir_all(&dir).map_err(|e| MicError::io(&dir, e))?;
 
-        // Matching by release params
-        matches.push(equals_same_value::<i32>(&self.year, &dt.year()));
-        matches.push(equals_same_value::<u32>(&self.month, &dt.month()));
-        matches.push(equals_same_value::<u32>(&self.day, &dt.day()));
-        // Matching by Decade
-        matches.push(match_decade(dt, &self.decade));
+    Ok(dir)
+}
 
-        // Matching by Track info
-        matches.push(equals_same_value::<String>(&self.artist, &cover.album_data.track_artist));
-        matches.push(equals_same_value::<String>(&self.album, &cover.album_data.album_title));
-        matches.push(equals_same_value::<String>(&self.track, &cover.album_data.track_number));
+/// Bytes taken by the thumbnails on disk
+pub fn thumbnail_usage() -> Result<u64, MicError> {
+    let dir = thumbnail_dir()?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| MicError::io(&dir, e))?;
 
+    Ok(entries.flatten().filter_map(|entry| entry.metadata().ok()).map(|m| m.len()).sum())
+}
 
-        // Matching by Current Week
-        if self.week {
-            matches.push(match_current_week(&cover.album_data.recording_date))
-        }
+/// Deletes thumbnails no cached cover refers to, ex. of a cover that was replaced or removed
+fn remove_unused_thumbnails<'a>(covers: impl Iterator<Item = &'a AlbumCoverData>) -> Result<(), MicError> {
+    let used: HashSet<&PathBuf> = covers.map(|cover| &cover.image.thumbnail).collect();
+    let dir = thumbnail_dir()?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| MicError::io(&dir, e))?;
 
-        if self.instrumental && cover.album_data.lyrics.len() == 0 {
-            matches.push(match_no_lyrics(&cover.album_data.lyrics));
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if !used.contains(&path) {
+            std::fs::remove_file(&path).map_err(|e| MicError::io(&path, e))?;
         }
-
-        matches.iter().all(|&check| check)
     }
+
+    Ok(())
 }
 
-impl From<DynamicImage> for ImageCache {
-    fn from(img: DynamicImage) -> Self {
-        let resize = img.resize_exact(480, 480, image::imageops::FilterType::Triangle);
-        let (width, height) = resize.dimensions();
-        let pixels = resize.to_rgb8().into_raw();
-        let points = vec_u8_to_vec_point(pixels.clone(), width);
-        let clusters = k_means(points, 5, 10);
-        let dominant_colors: Vec<(u8,u8,u8)> = clusters.iter().map(|cluster| cluster.centroid.color).collect();
+/// Deletes every thumbnail, used when the cover cache is cleared
+pub fn clear_thumbnails() -> Result<(), MicError> {
+    let dir = thumbnail_dir()?;
 
-        ImageCache { width, height, pixels, dominant_colors }
-    }
+    std::fs::remove_dir_all(&dir).map_err(|e| MicError::io(&dir, e))
 }
-const CACHE_FILE_NAME: &str = "cover_cache.json";
 
-pub fn get_album_covers() -> Result<Vec<AlbumCoverData>, Box<dyn Error>> {
-    let music_dir = dirs::audio_dir().unwrap();
-    let pattern = format!("{}/*/*/cover.*", music_dir.to_string_lossy());
+pub const CACHE_FILE_NAME: &str = "cover_cache.json";
 
-    Ok(load_covers(pattern)?)
+impl CacheRecord for AlbumCoverData {
+    const TABLE: &'static str = "covers";
+    const JSON_NAME: &'static str = CACHE_FILE_NAME;
+    const COLUMNS: &'static [&'static str] = &["album_artist", "album_title", "dir"];
+
+    fn indexed_columns(&self) -> Vec<String> {
+        vec![
+            self.album_data.album_artist.clone(),
+            self.album_data.album_title.clone(),
+            self.album_data.dir.clone(),
+        ]
+    }
+}
+
+pub fn get_album_covers(config: &Config) -> Result<Vec<AlbumCoverData>, Box<dyn Error>> {
+    load_covers(config, &get_albums(config)?)
 }
 
-/// Collects a list of songs by filter
-fn load_covers(pattern: String) -> Result<Vec<AlbumCoverData>, Box<dyn Error>> {
-    // rayon::ThreadPoolBuilder::new().num_threads(4).build_global()?;
-    let globs = glob_with(
-        &pattern,
-        MatchOptions {
-            case_sensitive: false,
-            require_literal_separator: false,
-            require_literal_leading_dot: false,
-        },
-    )?;
-    let cache_file = Arc::new(Mutex::new(load_cache_file::<AlbumCoverData>(CACHE_FILE_NAME)?));
-    let songs = get_albums()?;
-
-    let file_paths: Vec<_> = globs.filter_map(|entry| entry.ok()).collect();
-
-    file_paths.par_iter().for_each(|path| {
-        let filename = path.display().to_string();
-        let cache_file_ref = Arc::clone(&cache_file);
-        let mut cache_file_ref = cache_file_ref.lock().unwrap();
-
-        match cache_file_ref.data.entry(filename.clone()) {
-            Entry::Occupied(o) => {
-                o.into_mut()
-            },
-            Entry::Vacant(v) => {
-                let img = image::open(filename.clone()).unwrap();
-
-                let mut arr_file_dir = filename.split('/')
-                    .collect::<Vec<&str>>();
-                arr_file_dir.pop();
-                let dir = arr_file_dir.join("/");
-
-                // println!("New: {}", dir);
-                let song: Vec<_> = songs.iter()
-                    .filter(|s| s.filename
-                    .contains(&dir)).collect();
-
-                let ic: ImageCache = ImageCache::from(img);
-
-                v.insert(AlbumCoverData {
-                    image: ic,
-                    album_data: song[0].clone(),
-                    cover_name: filename,
-                })
-            },
-        };
+/// Decodes the cover of every album, covers cached since their last change are reused
+fn load_covers(config: &Config, albums: &[Album]) -> Result<Vec<AlbumCoverData>, Box<dyn Error>> {
+    let by_cover: HashMap<String, &Album> = albums
+        .iter()
+        .filter_map(|a| a.cover.as_ref().map(|cover| (cover.display().to_string(), a)))
+        .collect();
+    let (files, mut skipped) = fingerprint_files(config, by_cover.keys().cloned().collect());
+    let mut cache_file = load_cache_where::<AlbumCoverData, _>(is_unchanged(&files))?;
+
+    // A cover whose thumbnail was deleted is decoded again
+    let pending = pending_files(&cache_file, files, |cover| cover.image.thumbnail.exists());
+
+    let results = read_parallel(config, "Decoding covers", pending, |filename| {
+        let album = by_cover[filename];
+
+        Ok(AlbumCoverData {
+            image: ImageCache::create(filename)?,
+            album_data: album.head().clone(),
+            cover_name: filename.to_string(),
+        })
     });
+    skipped.extend(merge_results(&mut cache_file, results));
+
+    cache_file.prune_missing();
+    save_cache::<AlbumCoverData>(&cache_file)?;
+
+    // Covers outside the scanned roots weren't decoded, their thumbnails can't be told apart
+    if cache_file.is_complete() {
+        remove_unused_thumbnails(cache_file.values())?;
+    }
 
-    save_cache_file::<AlbumCoverData>(&cache_file.lock().unwrap(), CACHE_FILE_NAME)?;
+    report_skipped(&skipped);
 
-    let map_to_vec: Vec<AlbumCoverData> = {
-        let cache_file_ref = cache_file.lock().unwrap();
-        cache_file_ref.data.values().cloned().collect()
-    };
+    // Tags change without touching the cover, a reused cover takes them from the album as it is now
+    let map_to_vec: Vec<AlbumCoverData> = cache_file
+        .values()
+        .map(|cover| match by_cover.get(&cover.cover_name) {
+            Some(album) => AlbumCoverData { album_data: album.head().clone(), ..cover.clone() },
+            None => cover.clone(),
+        })
+        .collect();
 
     Ok(map_to_vec)
 }
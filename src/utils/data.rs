@@ -6,24 +6,17 @@ use regex::Regex;
 use super::kmeans::Point;
 
 pub fn array_truncate<T>(arr: &mut Vec<T>, len: Option<usize>) {
-    match len {
-        Some(len) => {
-            let new_length = arr.len().min(len);
-
-            arr.truncate(new_length);
-        },
-        None => (),
-    };
+    if let Some(len) = len {
+        let new_length = arr.len().min(len);
+
+        arr.truncate(new_length);
+    }
 }
 
 pub fn hashmap_to_vec_truple<K, V>(data: HashMap<K, V>) -> Vec<(K, V)> {
     data.into_iter().collect::<Vec<(K, V)>>()
 }
 
-pub fn string_clean(str: String) -> String {
-    str.to_lowercase()
-}
-
 pub fn string_to_vec(data: String, pattern: &str) -> Vec<String> {
     data.split(pattern).filter(|d| !d.is_empty()).map(|d| d.to_string()).collect()
 }
@@ -43,7 +36,7 @@ pub fn vec_u8_to_vec_point(pixels: Vec<u8>, width: u32) -> Vec<Point> {
 }
 
 pub fn validate_img_filename(name: &str) -> Result<bool, Box<dyn Error>> {
-    let file_extensions = vec!["png", "PNG", "jpeg", "JPG", "JPEG", "jpg"];
+    let file_extensions = ["png", "PNG", "jpeg", "JPG", "JPEG", "jpg"];
     let regex_str = format!("\\.({})$", file_extensions.join("|"));
     let regex = Regex::new(&regex_str)?;
 
@@ -1,22 +1,327 @@
-use chrono::{DateTime, Datelike, Local, NaiveDate, ParseError, Utc, Weekday};
+use std::{ops::Bound, str::FromStr};
 
-pub fn parse_string_to_datetime(input: &str) -> Result<DateTime<Utc>, ParseError> {
-    let naive_date = NaiveDate::parse_from_str(input, "%Y-%m-%d")?;
-    let naive_datetime = naive_date.and_hms_opt(0, 0, 0).unwrap();
-    Ok(DateTime::<Utc>::from_naive_utc_and_offset(naive_datetime, Utc))
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, Utc, Weekday};
+use serde::Deserialize;
+
+use super::error::MicError;
+
+/// Start and end of a range, either may be open
+pub type Range<T> = (Bound<T>, Bound<T>);
+
+/// Which date tag drives filtering and sorting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DateField {
+    /// RecordingDate, the tag most taggers write as "date"
+    #[default]
+    Recording,
+    /// OriginalReleaseDate
+    Release,
+}
+
+/// A date tag that may only hold a year, or a year and month
+///
+/// A partial date only matches filters at its own precision: "1994" matches a year or decade
+/// but never a month, day, week or anniversary, and only matches a date range that holds the whole year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PartialDate {
+    pub year: i32,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
 }
 
-pub fn parse_string_to_yearless_date(input: &str) -> NaiveDate {
-    let naive_date = NaiveDate::parse_from_str(input, "%Y-%m-%d").unwrap();
-    let yearless_date = NaiveDate::from_ymd_opt(0, naive_date.month(), naive_date.day()).unwrap();
+impl PartialDate {
+    /// Accepts "1994", "1994-05", "1994-05-10" and timestamps like "1994-05-10T00:00:00"
+    pub fn parse(input: &str) -> Result<PartialDate, MicError> {
+        let trimmed = input.trim();
+        let date = trimmed.split(['T', ' ']).next().unwrap_or_default();
+        let parts: Vec<&str> = date.split('-').collect();
+        let number = |part: &str| part.parse::<u32>().map_err(|_| MicError::date(input));
+
+        let partial = match parts.as_slice() {
+            [year] if year.len() == 4 => PartialDate { year: number(year)? as i32, month: None, day: None },
+            [year, month] if year.len() == 4 => {
+                PartialDate { year: number(year)? as i32, month: Some(number(month)?), day: None }
+            },
+            [year, month, day] if year.len() == 4 => {
+                PartialDate { year: number(year)? as i32, month: Some(number(month)?), day: Some(number(day)?) }
+            },
+            _ => return Err(MicError::date(input)),
+        };
+
+        // Rejects impossible months and days like 1994-13 or 1994-02-30
+        NaiveDate::from_ymd_opt(partial.year, partial.month.unwrap_or(1), partial.day.unwrap_or(1))
+            .ok_or_else(|| MicError::date(input))?;
+
+        Ok(partial)
+    }
+
+    /// The exact day, only for dates with day precision
+    pub fn full(&self) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(self.year, self.month?, self.day?)
+    }
+
+    pub fn first_day(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1)).unwrap()
+    }
+
+    pub fn last_day(&self) -> NaiveDate {
+        match (self.month, self.day) {
+            (Some(_), Some(_)) => self.first_day(),
+            (Some(_), None) => self.first_day() + Months::new(1) - Days::new(1),
+            _ => NaiveDate::from_ymd_opt(self.year, 12, 31).unwrap(),
+        }
+    }
+
+    pub fn decade(&self) -> i32 {
+        self.year / 10 * 10
+    }
+
+    /// Month and day for sorting by anniversary, missing parts sort first
+    pub fn yearless(&self) -> (Option<u32>, Option<u32>) {
+        (self.month, self.day)
+    }
+}
 
-    yearless_date
+impl std::fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.month, self.day) {
+            (Some(month), Some(day)) => write!(f, "{:04}-{:02}-{:02}", self.year, month, day),
+            (Some(month), None) => write!(f, "{:04}-{:02}", self.year, month),
+            _ => write!(f, "{:04}", self.year),
+        }
+    }
+}
+
+/// Parses a full date, partial dates are rejected
+pub fn parse_string_to_datetime(input: &str) -> Result<DateTime<Utc>, MicError> {
+    let naive_date = PartialDate::parse(input)?.full().ok_or_else(|| MicError::date(input))?;
+    let naive_datetime = naive_date.and_hms_opt(0, 0, 0).unwrap();
+    Ok(DateTime::<Utc>::from_naive_utc_and_offset(naive_datetime, Utc))
 }
 
 pub fn get_start_end_week_dates() -> (NaiveDate, NaiveDate) {
     let dt: DateTime<Local> = chrono::offset::Local::now();
     let naive_date = NaiveDate::from_ymd_opt(dt.year(), dt.month(), dt.day()).unwrap();
-    let week = naive_date.week(Weekday::Sat);
+
+    week_of(naive_date)
+}
+
+/// Parses "1985..1994" (inclusive), "1985..<1995" (end excluded), "1990.." or "..1994"
+pub fn parse_range<T, F>(input: &str, parse: F) -> Result<Range<T>, MicError>
+where
+    F: Fn(&str) -> Result<T, MicError>,
+{
+    let (start, end) = input
+        .split_once("..")
+        .ok_or_else(|| MicError::query(format!("\"{}\" is not a range like 1985..1994", input.trim())))?;
+    let (end, exclusive) = match end.strip_prefix('<') {
+        Some(end) => (end, true),
+        None => (end, false),
+    };
+
+    let start = match start.trim() {
+        "" => Bound::Unbounded,
+        start => Bound::Included(parse(start)?),
+    };
+    let end = match (end.trim(), exclusive) {
+        ("", _) => Bound::Unbounded,
+        (end, true) => Bound::Excluded(parse(end)?),
+        (end, false) => Bound::Included(parse(end)?),
+    };
+
+    Ok((start, end))
+}
+
+/// Parses a plain number for `parse_range`, ex. a year
+pub fn parse_number<T: FromStr>(input: &str) -> Result<T, MicError> {
+    input.trim().parse().map_err(|_| MicError::query(format!("\"{}\" is not a number", input.trim())))
+}
+
+/// Seconds of a length like "150", "90s", "2m30s", "1h5m" or "3:30", for `parse_range`
+pub fn parse_duration(input: &str) -> Result<u64, MicError> {
+    let invalid = || MicError::query(format!("\"{}\" is not a valid duration", input.trim()));
+    let trimmed = input.trim();
+
+    if trimmed.contains(':') {
+        return trimmed
+            .split(':')
+            .try_fold(0u64, |total, part| {
+                let part = part.parse::<u64>().ok()?;
+
+                total.checked_mul(60)?.checked_add(part)
+            })
+            .ok_or_else(invalid);
+    }
+
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+
+    for c in trimmed.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            },
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+
+        // Checked, a huge number is invalid rather than an overflow
+        seconds = number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(unit))
+            .and_then(|n| seconds.checked_add(n))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+
+    match (number.is_empty(), trimmed.is_empty()) {
+        (_, true) => Err(invalid()),
+        (true, false) => Ok(seconds),
+        (false, false) => number.parse::<u64>().ok().and_then(|n| seconds.checked_add(n)).ok_or_else(invalid),
+    }
+}
+
+/// A full date like "1994-02-01", for `parse_range`
+pub fn parse_date(input: &str) -> Result<NaiveDate, MicError> {
+    parse_string_to_datetime(input.trim())
+        .map(|date| date.date_naive())
+        .map_err(|_| MicError::query(format!("\"{}\" is not a date like 1994-02-01", input.trim())))
+}
+
+/// First and last day of a period relative to `today`, ex. "last 30 days", "next month" or "this week"
+pub fn parse_period(input: &str, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), MicError> {
+    let invalid = || MicError::query(format!("\"{}\" is not a period like \"last 30 days\"", input.trim()));
+    let words: Vec<String> = input.split_whitespace().map(|w| w.to_lowercase()).collect();
+    let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+
+    let (direction, count, unit) = match words.as_slice() {
+        ["today"] => return Ok((today, today)),
+        ["this", "week"] => return Ok(week_of(today)),
+        ["this", "month"] => {
+            let start = today.with_day(1).unwrap();
+            return Ok((start, start + Months::new(1) - Days::new(1)));
+        },
+        ["this", "year"] => {
+            let start = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap();
+            return Ok((start, NaiveDate::from_ymd_opt(today.year(), 12, 31).unwrap()));
+        },
+        [direction, unit] => (*direction, 1, *unit),
+        [direction, count, unit] => (*direction, parse_number::<u32>(count).map_err(|_| invalid())?, *unit),
+        _ => return Err(invalid()),
+    };
+
+    let shift = |date: NaiveDate, forward: bool| -> Option<NaiveDate> {
+        match (unit.trim_end_matches('s'), forward) {
+            ("day", true) => date.checked_add_days(Days::new(count as u64)),
+            ("day", false) => date.checked_sub_days(Days::new(count as u64)),
+            ("week", true) => date.checked_add_days(Days::new(count as u64 * 7)),
+            ("week", false) => date.checked_sub_days(Days::new(count as u64 * 7)),
+            ("month", true) => date.checked_add_months(Months::new(count)),
+            ("month", false) => date.checked_sub_months(Months::new(count)),
+            ("year", true) => date.checked_add_months(Months::new(count.checked_mul(12)?)),
+            ("year", false) => date.checked_sub_months(Months::new(count.checked_mul(12)?)),
+            _ => None,
+        }
+    };
+
+    match direction {
+        "last" | "past" => shift(today, false).map(|start| (start, today)),
+        "next" | "coming" => shift(today, true).map(|end| (today, end)),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
+
+/// Whether the anniversary of `date` falls between `start` and `end`, Feb 29 counts as Feb 28 in other years
+pub fn anniversary_between(date: NaiveDate, start: NaiveDate, end: NaiveDate) -> bool {
+    (start.year()..=end.year()).any(|year| {
+        let anniversary = NaiveDate::from_ymd_opt(year, date.month(), date.day())
+            .or_else(|| NaiveDate::from_ymd_opt(year, date.month(), date.day() - 1));
+
+        anniversary.is_some_and(|a| a >= start && a <= end)
+    })
+}
+
+fn week_of(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let week = date.week(Weekday::Sat);
 
     (week.first_day(), week.last_day())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ops::{Bound, RangeBounds};
+
+    use chrono::NaiveDate;
+
+    use super::{anniversary_between, parse_duration, parse_number, parse_period, parse_range, PartialDate};
+
+    #[test]
+    fn parses_partial_dates() {
+        let year = PartialDate::parse("1994").unwrap();
+        let month = PartialDate::parse("1994-05").unwrap();
+        let stamp = PartialDate::parse("1994-05-10T00:00:00").unwrap();
+
+        assert_eq!((year.month, year.day), (None, None));
+        assert_eq!(month.last_day(), NaiveDate::from_ymd_opt(1994, 5, 31).unwrap());
+        assert_eq!(stamp.full(), NaiveDate::from_ymd_opt(1994, 5, 10));
+        assert!(PartialDate::parse("1994-13").is_err());
+        assert!(PartialDate::parse("").is_err());
+    }
+
+    #[test]
+    fn parses_year_ranges() {
+        let inclusive = parse_range::<i32, _>("1985..1994", parse_number).unwrap();
+        let exclusive = parse_range::<i32, _>("1985..<1994", parse_number).unwrap();
+
+        assert!(inclusive.contains(&1994));
+        assert!(!exclusive.contains(&1994));
+        assert_eq!(parse_range::<i32, _>("1990..", parse_number).unwrap().1, Bound::Unbounded);
+
+        // Bad flags are usage errors, like a bad --where
+        for input in ["1985", "19x5..1994", "1985..<x"] {
+            assert_eq!(parse_range::<i32, _>(input, parse_number).map(|_| ()).unwrap_err().exit_code(), 2, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_durations() {
+        let range = parse_range("2m..6m", parse_duration).unwrap();
+
+        assert_eq!(range, (Bound::Included(120), Bound::Included(360)));
+        assert_eq!(parse_duration("1h2m3s").unwrap(), 3723);
+        assert_eq!(parse_duration("3:30").unwrap(), 210);
+        assert_eq!(parse_duration("150").unwrap(), 150);
+        assert!(parse_duration("2x").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("9999999999999999999h").is_err());
+        assert!(parse_duration("5124095576030431h1h").is_err());
+        assert!(parse_duration("9999999999999999999:00").is_err());
+    }
+
+    #[test]
+    fn parses_relative_periods() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let (start, end) = parse_period("last 30 days", today).unwrap();
+
+        assert_eq!(start, NaiveDate::from_ymd_opt(2024, 2, 14).unwrap());
+        assert_eq!(end, today);
+        assert_eq!(parse_period("next fortnight", today).unwrap_err().exit_code(), 2);
+        assert_eq!(parse_period("last x days", today).unwrap_err().exit_code(), 2);
+        assert_eq!(parse_period("last 400000000 years", today).unwrap_err().exit_code(), 2);
+    }
+
+    #[test]
+    fn anniversaries_wrap_around_the_year() {
+        let start = NaiveDate::from_ymd_opt(2023, 12, 20).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 1, 10).unwrap();
+
+        assert!(anniversary_between(NaiveDate::from_ymd_opt(1991, 1, 2).unwrap(), start, end));
+        assert!(!anniversary_between(NaiveDate::from_ymd_opt(1991, 2, 2).unwrap(), start, end));
+    }
+}
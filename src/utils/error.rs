@@ -0,0 +1,103 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Failures that can happen while reading a library, see the README for exit codes
+#[derive(Debug)]
+pub enum MicError {
+    Io { path: PathBuf, source: io::Error },
+    Tag { path: String, message: String },
+    /// The file is known when the date comes from a song's tags
+    Date { path: Option<String>, value: String },
+    Image { path: String, message: String },
+    Cache { message: String },
+    Config { message: String },
+    Query { message: String },
+}
+
+impl MicError {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> MicError {
+        MicError::Io { path: path.into(), source }
+    }
+
+    pub fn tag(path: &str, message: impl ToString) -> MicError {
+        MicError::Tag { path: path.to_string(), message: message.to_string() }
+    }
+
+    pub fn date(value: &str) -> MicError {
+        MicError::Date { path: None, value: value.to_string() }
+    }
+
+    /// Ties a failure while reading a song's tags to its file, dates stay date errors
+    pub fn in_file(self, path: &str) -> MicError {
+        match self {
+            MicError::Date { value, .. } => MicError::Date { path: Some(path.to_string()), value },
+            other => MicError::tag(path, other),
+        }
+    }
+
+    pub fn image(path: &str, message: impl ToString) -> MicError {
+        MicError::Image { path: path.to_string(), message: message.to_string() }
+    }
+
+    pub fn cache(message: impl ToString) -> MicError {
+        MicError::Cache { message: message.to_string() }
+    }
+
+    pub fn config(message: impl ToString) -> MicError {
+        MicError::Config { message: message.to_string() }
+    }
+
+    pub fn query(message: impl ToString) -> MicError {
+        MicError::Query { message: message.to_string() }
+    }
+
+    /// Process exit code for this kind of failure
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            MicError::Io { .. } => 3,
+            MicError::Tag { .. } => 4,
+            MicError::Date { .. } => 5,
+            MicError::Image { .. } => 6,
+            MicError::Cache { .. } => 7,
+            MicError::Config { .. } => 8,
+            // A bad --where expression or pattern is a usage error, same as clap's
+            MicError::Query { .. } => 2,
+        }
+    }
+}
+
+impl fmt::Display for MicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MicError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            MicError::Tag { path, message } => write!(f, "{}: bad tags, {}", path, message),
+            MicError::Date { path: Some(path), value } => write!(f, "{}: \"{}\" is not a valid date", path, value),
+            MicError::Date { path: None, value } => write!(f, "\"{}\" is not a valid date", value),
+            MicError::Image { path, message } => write!(f, "{}: bad image, {}", path, message),
+            MicError::Cache { message } => write!(f, "cache: {}", message),
+            MicError::Config { message } => write!(f, "config: {}", message),
+            MicError::Query { message } => write!(f, "filter: {}", message),
+        }
+    }
+}
+
+impl Error for MicError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MicError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Prints the files a scan had to leave out
+pub fn report_skipped(skipped: &[MicError]) {
+    if skipped.is_empty() {
+        return;
+    }
+
+    eprintln!("Skipped {} file(s):", skipped.len());
+
+    for error in skipped {
+        eprintln!("  {}", error);
+    }
+}
@@ -1,66 +1,334 @@
-use chrono::{DateTime, Utc, Datelike, NaiveDate};
+use std::ops::RangeBounds;
 
-use super::{date::{get_start_end_week_dates, parse_string_to_yearless_date}, data::string_clean};
+use chrono::{Datelike, NaiveDate};
+use glob::Pattern;
+use regex::Regex;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
 
-pub fn contains_list_of_strings (needles: &Option<String>, haystack: &Vec<String>) -> bool {
-    match needles {
-        Some(string) => string
-            .split(',')
-            .map(|m| m.to_string())
-            .any(|needle| haystack.contains(&needle)),
-        None => true
+use super::{
+    date::{anniversary_between, get_start_end_week_dates, DateField, PartialDate, Range},
+    error::{report_skipped, MicError},
+    songs::SongData,
+};
+
+/// Anything filtered through the tags of a song, ex. a track, an album or a cover
+pub trait HasSongData {
+    fn song_data(&self) -> &SongData;
+}
+
+/// A condition on a song's tags, conditions combine with `and`
+pub trait Predicate {
+    /// Fails when a tag the condition needs can't be read, ex. an unparseable date
+    fn matches(&self, song: &SongData) -> Result<bool, MicError>;
+
+    /// Keeps matching items, items whose tags can't be matched are skipped and reported
+    fn filter<T: HasSongData>(&self, items: Vec<T>) -> Vec<T> {
+        let mut skipped: Vec<MicError> = vec![];
+        let filtered = items
+            .into_iter()
+            .filter(|item| match self.matches(item.song_data()) {
+                Ok(matches) => matches,
+                Err(e) => {
+                    skipped.push(e.in_file(&item.song_data().filename));
+                    false
+                },
+            })
+            .collect();
+
+        report_skipped(&skipped);
+
+        filtered
+    }
+
+    fn and<P: Predicate>(self, other: P) -> And<Self, P>
+    where
+        Self: Sized,
+    {
+        And(self, other)
     }
 }
 
-pub fn match_current_week(release: &String) -> bool {
-    let (sat, fri) = get_start_end_week_dates();
-    let (yearless_sat, yearless_fri) = (
-        NaiveDate::from_ymd_opt(0, sat.month(), sat.day()).unwrap(),
-        NaiveDate::from_ymd_opt(0, fri.month(), fri.day()).unwrap(),
-    );
-    let yearless_date = parse_string_to_yearless_date(release);
+/// Matches when both conditions do
+pub struct And<A, B>(A, B);
+
+impl<A: Predicate, B: Predicate> Predicate for And<A, B> {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        Ok(self.0.matches(song)? && self.1.matches(song)?)
+    }
+}
+
+/// Lets one condition be shared by several filters
+impl<P: Predicate> Predicate for &P {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        (*self).matches(song)
+    }
+}
 
-    yearless_date.ge(&yearless_sat) && yearless_date.le(&yearless_fri)
+/// A condition that wasn't given matches everything
+impl<P: Predicate> Predicate for Option<P> {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        match self {
+            Some(predicate) => predicate.matches(song),
+            None => Ok(true),
+        }
+    }
 }
 
-pub fn match_items_left(dt: &str) -> bool {
-    let yearless_dt = parse_string_to_yearless_date(dt);
-    let yearless_today = parse_string_to_yearless_date(&(chrono::offset::Local::now().to_string()));
+/// Ranges of release years, decades, dates and anniversaries, every range given has to match
+#[derive(Default)]
+pub struct DateRangeFilter {
+    pub date_field: DateField,
+    pub years: Option<Range<i32>>,
+    /// Decades by their first year, "1980..1990" covers the 80s and 90s
+    pub decades: Option<Range<i32>>,
+    pub dates: Vec<Range<NaiveDate>>,
+    /// First and last day the anniversary of the release has to fall on
+    pub anniversary: Option<(NaiveDate, NaiveDate)>,
+}
 
-    yearless_dt >= yearless_today
+impl DateRangeFilter {
+    fn is_empty(&self) -> bool {
+        self.years.is_none() && self.decades.is_none() && self.dates.is_empty() && self.anniversary.is_none()
+    }
 }
 
-pub fn match_decade(dt: DateTime<Utc>, decade: &Option<u16>) -> bool {
-    let year = dt.year();
-    let decade_year = ((year / 10) * 10) as u16;
+impl Predicate for DateRangeFilter {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        if self.is_empty() {
+            return Ok(true);
+        }
 
-    equals_same_value::<u16>(decade, &decade_year)
+        // A song without a date is in no range
+        let Some(date) = song.date(self.date_field)? else {
+            return Ok(false);
+        };
+
+        // A partial date has to fall in a range as a whole
+        Ok(self.years.is_none_or(|years| years.contains(&date.year))
+            && self.decades.is_none_or(|decades| decades.contains(&date.decade()))
+            && self.dates.iter().all(|dates| dates.contains(&date.first_day()) && dates.contains(&date.last_day()))
+            && self.anniversary.is_none_or(|(start, end)| {
+                date.full().is_some_and(|full| anniversary_between(full, start, end))
+            }))
+    }
 }
 
-pub fn match_no_lyrics(lyrics: &Vec<String>) -> bool {
-    lyrics.len() == 0
+/// Drops songs with an excluded genre, mood, artist or lyric word
+#[derive(Default)]
+pub struct ExcludeFilter {
+    pub genres: Option<TextMatcher>,
+    pub moods: Option<TextMatcher>,
+    /// Checked against both the track and album artist
+    pub artists: Option<TextMatcher>,
+    /// Checked against every line and every word of the lyrics
+    pub words: Option<TextMatcher>,
 }
 
-pub fn match_lyrics_contain_words(words: &Option<String>, lyrics: &Vec<String>) -> bool {
-    match words {
-        Some(words) => {
-            words.split(',').map(|w| w.to_lowercase().to_string()).any(|word| {
-                let mut has_word = false;
-                for phrases in lyrics.clone() {
-                    let cleaned = string_clean(phrases);
+impl Predicate for ExcludeFilter {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        let excluded = |matcher: &Option<TextMatcher>, haystack: &[String]| {
+            matcher.as_ref().is_some_and(|m| m.matches_any(haystack))
+        };
+
+        Ok(!(excluded(&self.genres, &song.genre)
+            || excluded(&self.moods, &song.mood)
+            || excluded(&self.artists, &[song.track_artist.clone(), song.album_artist.clone()])
+            || self.words.as_ref().is_some_and(|m| match_lyrics(m, &song.lyrics))))
+    }
+}
 
-                    if cleaned.contains(&word) {
-                        has_word = true;
-                    }
-                }
+/// Credits and release details, every field given has to match
+#[derive(Default)]
+pub struct DetailFilter {
+    /// Anyone credited in any role
+    pub credit: Option<TextMatcher>,
+    pub label: Option<TextMatcher>,
+    pub language: Option<TextMatcher>,
+    pub catalog: Option<TextMatcher>,
+}
 
-                has_word
-            })
-        },
-        None => true
+impl Predicate for DetailFilter {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        Ok(self.credit.as_ref().is_none_or(|m| song.credits().any(|name| m.matches(name)))
+            && match_text(&self.label, &song.label)
+            && match_text(&self.language, &song.language)
+            && match_text(&self.catalog, &song.catalog_number))
     }
 }
 
+/// Codec and quality of the audio, every property given has to match
+#[derive(Default)]
+pub struct AudioFilter {
+    pub lossless: bool,
+    pub formats: Option<TextMatcher>,
+    /// Kbps, files with an unknown bitrate never match
+    pub bitrate: Option<Range<u32>>,
+    pub sample_rate: Option<Range<u32>>,
+    pub bit_depth: Option<Range<u8>>,
+    pub channels: Option<u8>,
+    /// Seconds
+    pub duration: Option<Range<u64>>,
+}
+
+impl Predicate for AudioFilter {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        let known = |range: &Option<Range<u32>>, value: u32| {
+            range.as_ref().is_none_or(|range| value > 0 && range.contains(&value))
+        };
+
+        Ok((!self.lossless || song.is_lossless())
+            && match_text(&self.formats, &song.format)
+            && known(&self.bitrate, song.bitrate)
+            && known(&self.sample_rate, song.sample_rate)
+            && self.bit_depth.is_none_or(|range| song.bit_depth > 0 && range.contains(&song.bit_depth))
+            && self.channels.is_none_or(|channels| song.channels == channels)
+            && self.duration.is_none_or(|range| range.contains(&song.track_length)))
+    }
+}
+
+impl HasSongData for SongData {
+    fn song_data(&self) -> &SongData {
+        self
+    }
+}
+
+/// How text filters compare their value with a tag, every mode ignores case and accents
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum MatchMode {
+    /// The whole tag
+    #[default]
+    Exact,
+    /// Part of the tag
+    Substring,
+    /// Shell style pattern, ex. "the *"
+    Glob,
+    /// Regular expression searched for in the tag
+    Regex,
+}
+
+/// Compiled text filter, holds several alternatives when built from a comma separated list
+#[derive(Debug, Clone)]
+pub struct TextMatcher {
+    needles: Vec<Needle>,
+}
+
+#[derive(Debug, Clone)]
+enum Needle {
+    Exact(String),
+    Substring(String),
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl TextMatcher {
+    pub fn new(needle: &str, mode: MatchMode) -> Result<TextMatcher, MicError> {
+        let needle = match mode {
+            MatchMode::Exact => Needle::Exact(fold(needle)),
+            MatchMode::Substring => Needle::Substring(fold(needle)),
+            MatchMode::Glob => Needle::Glob(Pattern::new(&fold(needle)).map_err(|e| invalid_pattern(needle, e))?),
+            // Lowercasing the pattern would break escapes like \W, the regex ignores case instead
+            MatchMode::Regex => Needle::Regex(
+                Regex::new(&format!("(?i){}", fold_accents(needle))).map_err(|e| invalid_pattern(needle, e))?,
+            ),
+        };
+
+        Ok(TextMatcher { needles: vec![needle] })
+    }
+
+    /// Matches any value of a comma separated list ex. "synth,metal", regexes aren't split
+    pub fn any_of(needles: &str, mode: MatchMode) -> Result<TextMatcher, MicError> {
+        if mode == MatchMode::Regex {
+            return TextMatcher::new(needles, mode);
+        }
+
+        TextMatcher::any(needles.split(','), mode)
+    }
+
+    /// Matches any of the given values
+    pub fn any<'a>(needles: impl IntoIterator<Item = &'a str>, mode: MatchMode) -> Result<TextMatcher, MicError> {
+        let needles = needles
+            .into_iter()
+            .map(|needle| TextMatcher::new(needle.trim(), mode).map(|m| m.needles))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(TextMatcher { needles: needles.into_iter().flatten().collect() })
+    }
+
+    pub fn matches(&self, haystack: &str) -> bool {
+        let folded = fold(haystack);
+
+        self.needles.iter().any(|needle| match needle {
+            Needle::Exact(needle) => folded == *needle,
+            Needle::Substring(needle) => folded.contains(needle.as_str()),
+            Needle::Glob(pattern) => pattern.matches(&folded),
+            Needle::Regex(regex) => regex.is_match(&fold_accents(haystack)),
+        })
+    }
+
+    pub fn matches_any(&self, haystack: &[String]) -> bool {
+        haystack.iter().any(|item| self.matches(item))
+    }
+}
+
+fn invalid_pattern(pattern: &str, error: impl std::fmt::Display) -> MicError {
+    MicError::query(format!("invalid pattern \"{}\", {}", pattern, error))
+}
+
+/// Lowercases and strips accents so "Björk" compares equal to "bjork"
+pub fn fold(text: &str) -> String {
+    fold_accents(text).to_lowercase()
+}
+
+fn fold_accents(text: &str) -> String {
+    text.nfkd().filter(|c| !is_combining_mark(*c)).nfc().collect()
+}
+
+/// Passes when no matcher was given
+pub fn match_text(matcher: &Option<TextMatcher>, haystack: &str) -> bool {
+    matcher.as_ref().is_none_or(|m| m.matches(haystack))
+}
+
+/// Passes when no matcher was given, otherwise any item has to match
+pub fn match_any_text(matcher: &Option<TextMatcher>, haystack: &[String]) -> bool {
+    matcher.as_ref().is_none_or(|m| m.matches_any(haystack))
+}
+
+/// Anniversary falls in the current week, needs a full date
+pub fn match_current_week(release: &PartialDate) -> bool {
+    let (sat, fri) = get_start_end_week_dates();
+
+    release.full().is_some_and(|date| anniversary_between(date, sat, fri))
+}
+
+/// Anniversary is still to come this year, needs a full date
+pub fn match_items_left(release: &PartialDate) -> bool {
+    let today = chrono::offset::Local::now().date_naive();
+
+    release.full().is_some_and(|date| (date.month(), date.day()) >= (today.month(), today.day()))
+}
+
+pub fn match_decade(release: &PartialDate, decade: &Option<u16>) -> bool {
+    equals_same_value::<u16>(decade, &(release.decade() as u16))
+}
+
+pub fn match_no_lyrics(lyrics: &[String]) -> bool {
+    lyrics.is_empty()
+}
+
+/// Any line or any word of the lyrics matches, "santa" excludes "Here comes Santa" but not "Santana"
+fn match_lyrics(matcher: &TextMatcher, lyrics: &[String]) -> bool {
+    lyrics
+        .iter()
+        .flat_map(|phrase| phrase.lines())
+        .any(|line| matcher.matches(line) || line.unicode_words().any(|word| matcher.matches(word)))
+}
+
+/// Passes when no matcher was given, otherwise a line or word of the lyrics has to match
+pub fn match_lyrics_contain_words(words: &Option<TextMatcher>, lyrics: &[String]) -> bool {
+    words.as_ref().is_none_or(|m| match_lyrics(m, lyrics))
+}
+
 pub fn equals_same_value<T>(needle: &Option<T>, haystack: &T) -> bool 
 where T: PartialEq<T> {
     match needle {
@@ -68,3 +336,152 @@ where T: PartialEq<T> {
         None => true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound::{Excluded, Included};
+
+    use super::{fold, DateRangeFilter, ExcludeFilter, MatchMode, Predicate, TextMatcher};
+    use crate::utils::{albums::group_albums, songs::SongData};
+
+    fn matcher(needle: &str, mode: MatchMode) -> TextMatcher {
+        TextMatcher::new(needle, mode).unwrap()
+    }
+
+    #[test]
+    fn folds_case_and_accents() {
+        assert_eq!(fold("Björk Guðmundsdóttir"), "bjork guðmundsdottir");
+        assert_eq!(fold("ＡＢＢＡ"), "abba");
+    }
+
+    #[test]
+    fn matches_in_every_mode() {
+        assert!(matcher("green day", MatchMode::Exact).matches("Green Day"));
+        assert!(!matcher("green", MatchMode::Exact).matches("Green Day"));
+        assert!(matcher("een d", MatchMode::Substring).matches("Green Day"));
+        assert!(matcher("the *", MatchMode::Glob).matches("The Cure"));
+        assert!(!matcher("the *", MatchMode::Glob).matches("Cure, The"));
+        assert!(matcher("^the (cure|smiths)$", MatchMode::Regex).matches("The Smiths"));
+        assert!(!matcher("^cure", MatchMode::Regex).matches("The Cure"));
+    }
+
+    #[test]
+    fn splits_lists_except_regexes() {
+        let genres = TextMatcher::any_of("synth pop, metal", MatchMode::Exact).unwrap();
+
+        assert!(genres.matches("Metal") && genres.matches("Synth Pop") && !genres.matches("pop"));
+        assert!(genres.matches_any(&["jazz".to_string(), "metal".to_string()]));
+        assert!(TextMatcher::any_of("^a{1,2}$", MatchMode::Regex).unwrap().matches("aa"));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let error = |needle: &str, mode: MatchMode| TextMatcher::new(needle, mode).unwrap_err().to_string();
+
+        assert!(error("(unclosed", MatchMode::Regex).starts_with("filter: invalid pattern \"(unclosed\""));
+        assert!(error("[a-", MatchMode::Glob).starts_with("filter: invalid pattern \"[a-\""));
+    }
+
+    #[test]
+    fn regex_ignores_accents_in_the_pattern() {
+        let matcher = TextMatcher::new("^björk$", MatchMode::Regex).unwrap();
+
+        assert!(matcher.matches("Björk") && matcher.matches("BJORK"));
+        assert!(TextMatcher::new(r"sigur\W+ros", MatchMode::Regex).unwrap().matches("Sigur Rós"));
+    }
+
+    fn song(album: &str, genre: &str, date: &str) -> SongData {
+        SongData {
+            album_artist: "Sonic Youth".to_string(),
+            album_title: album.to_string(),
+            dir: album.to_string(),
+            filename: format!("{}/01.flac", album),
+            genre: vec![genre.to_string()],
+            recording_date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn nineties() -> DateRangeFilter {
+        DateRangeFilter { years: Some((Included(1990), Excluded(2000))), ..Default::default() }
+    }
+
+    fn no_punk() -> ExcludeFilter {
+        ExcludeFilter { genres: Some(matcher("punk", MatchMode::Exact)), ..Default::default() }
+    }
+
+    #[test]
+    fn and_needs_both_conditions() {
+        let filter = nineties().and(no_punk());
+
+        assert!(filter.matches(&song("Dirty", "noise rock", "1992")).unwrap());
+        assert!(!filter.matches(&song("Dirty", "punk", "1992")).unwrap());
+        assert!(!filter.matches(&song("Sonic Nurse", "noise rock", "2004")).unwrap());
+        assert!(filter.matches(&song("Dirty", "noise rock", "someday")).is_err());
+        // A missing date is no date rather than a bad one
+        assert!(!filter.matches(&song("Dirty", "noise rock", "")).unwrap());
+    }
+
+    #[test]
+    fn missing_condition_matches_everything() {
+        let song = song("Dirty", "punk", "1992");
+
+        assert!(None::<ExcludeFilter>.matches(&song).unwrap());
+        assert!(!Some(no_punk()).matches(&song).unwrap());
+        assert!(!nineties().and(None::<DateRangeFilter>).and(Some(no_punk())).matches(&song).unwrap());
+    }
+
+    #[test]
+    fn filters_songs_and_albums_alike() {
+        let songs = vec![
+            song("Goo", "noise rock", "1990"),
+            song("Dirty", "punk", "1992"),
+            song("Sonic Nurse", "noise rock", "2004"),
+            song("Unknown", "noise rock", "someday"),
+        ];
+        let filter = nineties().and(None::<ExcludeFilter>).and(Some(no_punk()));
+
+        let kept_songs: Vec<String> = filter.filter(songs.clone()).into_iter().map(|s| s.album_title).collect();
+        let mut kept_albums: Vec<String> = filter.filter(group_albums(songs)).into_iter().map(|a| a.title).collect();
+        kept_albums.sort();
+
+        assert_eq!(kept_songs, vec!["Goo"]);
+        assert_eq!(kept_albums, kept_songs);
+    }
+
+    #[test]
+    fn excludes_genres_moods_and_artists() {
+        let exclude = ExcludeFilter {
+            moods: Some(TextMatcher::any_of("happy,upbeat", MatchMode::Exact).unwrap()),
+            artists: Some(matcher("various artists", MatchMode::Exact)),
+            ..Default::default()
+        };
+        let dirty = song("Dirty", "noise rock", "1992");
+
+        assert!(exclude.matches(&dirty).unwrap());
+
+        let upbeat = SongData { mood: vec!["Dark".to_string(), "Upbeat".to_string()], ..dirty.clone() };
+
+        assert!(!exclude.matches(&upbeat).unwrap());
+        // The album artist is checked too
+        assert!(!exclude.matches(&SongData { album_artist: "Various Artists".to_string(), ..dirty.clone() }).unwrap());
+        assert!(!no_punk().matches(&song("Dirty", "Punk", "1992")).unwrap());
+        assert!(ExcludeFilter::default().matches(&dirty).unwrap());
+    }
+
+    #[test]
+    fn excludes_lyrics_by_word_or_line() {
+        let lyrics = |text: &str| SongData { lyrics: vec![text.to_string()], ..song("Dirty", "punk", "1992") };
+        let exclude = |needle: &str, mode: MatchMode| ExcludeFilter {
+            words: Some(matcher(needle, mode)),
+            ..Default::default()
+        };
+
+        assert!(!exclude("santa", MatchMode::Exact).matches(&lyrics("Here comes Santa Claus")).unwrap());
+        assert!(exclude("santa", MatchMode::Exact).matches(&lyrics("Oye como va, Santana")).unwrap());
+        assert!(!exclude("santa", MatchMode::Substring).matches(&lyrics("Oye como va, Santana")).unwrap());
+        assert!(!exclude("silent night", MatchMode::Exact).matches(&lyrics("Silent Night\nHoly night")).unwrap());
+        assert!(!exclude("no(ë|e)l", MatchMode::Regex).matches(&lyrics("The first Noël")).unwrap());
+        assert!(exclude("santa", MatchMode::Exact).matches(&lyrics("")).unwrap());
+    }
+}
@@ -33,7 +33,7 @@ fn assign_to_clusters(points: &[Point], clusters: &mut [Cluster]) {
     }
 
     for point in points {
-        let closet_cluster = clusters.into_iter().min_by_key(|cluster| {
+        let closet_cluster = clusters.iter_mut().min_by_key(|cluster| {
             let dx = point.x as i32 - cluster.centroid.x as i32;
             let dy = point.y as i32 - cluster.centroid.y as i32;
             (dx * dx + dy * dy) as u32
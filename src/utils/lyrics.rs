@@ -0,0 +1,376 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{config::LyricsConfig, songs::SongData};
+
+/// Languages with a built-in stopword list and stemmer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+    Portuguese,
+    Italian,
+}
+
+const LANGUAGES: [Language; 6] = [
+    Language::English,
+    Language::Spanish,
+    Language::French,
+    Language::German,
+    Language::Portuguese,
+    Language::Italian,
+];
+
+impl Language {
+    /// From a language tag like "eng", "en" or "English"
+    pub fn from_tag(tag: &str) -> Option<Language> {
+        Some(match tag.trim().to_lowercase().as_str() {
+            "en" | "eng" | "english" => Language::English,
+            "es" | "spa" | "spanish" => Language::Spanish,
+            "fr" | "fra" | "fre" | "french" => Language::French,
+            "de" | "deu" | "ger" | "german" => Language::German,
+            "pt" | "por" | "portuguese" => Language::Portuguese,
+            "it" | "ita" | "italian" => Language::Italian,
+            _ => return None,
+        })
+    }
+
+    fn stopwords(&self) -> &'static str {
+        match self {
+            Language::English => include_str!("stopwords/english.txt"),
+            Language::Spanish => include_str!("stopwords/spanish.txt"),
+            Language::French => include_str!("stopwords/french.txt"),
+            Language::German => include_str!("stopwords/german.txt"),
+            Language::Portuguese => include_str!("stopwords/portuguese.txt"),
+            Language::Italian => include_str!("stopwords/italian.txt"),
+        }
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            Language::English => Algorithm::English,
+            Language::Spanish => Algorithm::Spanish,
+            Language::French => Algorithm::French,
+            Language::German => Algorithm::German,
+            Language::Portuguese => Algorithm::Portuguese,
+            Language::Italian => Algorithm::Italian,
+        }
+    }
+}
+
+/// Splits lyrics into words or phrases, dropping stopwords of the song's language
+pub struct Tokenizer {
+    stopwords: HashMap<Language, HashSet<String>>,
+    stemmers: Option<HashMap<Language, Stemmer>>,
+    /// Used for songs without a known language tag
+    languages: Vec<Language>,
+    min_length: usize,
+}
+
+impl Tokenizer {
+    pub fn new(config: &LyricsConfig, stem: bool) -> Tokenizer {
+        let stopwords = LANGUAGES
+            .iter()
+            .map(|language| {
+                let mut words: HashSet<String> = language.stopwords().split_whitespace().map(normalize).collect();
+                words.extend(config.stopwords.get(language).into_iter().flatten().map(|w| normalize(w)));
+
+                (*language, words)
+            })
+            .collect();
+
+        let stemmers = (stem || config.stem).then(|| {
+            LANGUAGES.iter().map(|language| (*language, Stemmer::create(language.algorithm()))).collect()
+        });
+
+        Tokenizer { stopwords, stemmers, languages: config.languages.clone(), min_length: config.min_length }
+    }
+
+    /// Words, or phrases of `n` words, of every line of the lyrics
+    ///
+    /// Phrases never cross a line and can't start or end with a stopword, "in the end" is kept but "the end of" isn't.
+    pub fn tokens(&self, song: &SongData, n: usize) -> Vec<String> {
+        let languages = match Language::from_tag(&song.language) {
+            Some(language) => vec![language],
+            None => self.languages.clone(),
+        };
+        let is_stopword = |word: &str| {
+            word.chars().count() < self.min_length || languages.iter().any(|l| self.stopwords[l].contains(word))
+        };
+        let stemmer = self.stemmers.as_ref().zip(languages.first()).map(|(stemmers, language)| &stemmers[language]);
+
+        let mut tokens: Vec<String> = vec![];
+
+        for line in song.lyrics.iter().flat_map(|phrase| phrase.lines()) {
+            let words = words(line);
+
+            for window in words.windows(n.max(1)) {
+                if is_stopword(&window[0]) || is_stopword(&window[window.len() - 1]) {
+                    continue;
+                }
+
+                let stemmed: Vec<String> = match stemmer {
+                    Some(stemmer) => window.iter().map(|w| stemmer.stem(w).to_string()).collect(),
+                    None => window.to_vec(),
+                };
+
+                tokens.push(stemmed.join(" "));
+            }
+        }
+
+        tokens
+    }
+}
+
+/// How the words of a group are scored against the rest of the library
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum Scoring {
+    /// Weighted log-odds ratio with the whole library as prior, a z-score
+    #[default]
+    LogOdds,
+    /// Term frequency in the group times the inverse of how many groups use the word
+    TfIdf,
+}
+
+/// Word counts of every group, ex. of every artist
+pub struct Vocabulary<K> {
+    groups: HashMap<K, HashMap<String, usize>>,
+    totals: HashMap<String, usize>,
+    /// Number of groups using each word
+    used_by: HashMap<String, usize>,
+}
+
+impl<K> Default for Vocabulary<K> {
+    fn default() -> Self {
+        Vocabulary { groups: HashMap::new(), totals: HashMap::new(), used_by: HashMap::new() }
+    }
+}
+
+impl<K: Hash + Eq> Vocabulary<K> {
+    /// Adds the words of a song to each of its groups, the library counts them once however many groups it has
+    pub fn add(&mut self, keys: Vec<K>, tokens: &[String]) {
+        for token in tokens {
+            *self.totals.entry(token.clone()).or_insert(0) += 1;
+        }
+
+        for key in keys {
+            let counts = self.groups.entry(key).or_default();
+
+            for token in tokens {
+                let count = counts.entry(token.clone()).or_insert(0);
+
+                if *count == 0 {
+                    *self.used_by.entry(token.clone()).or_insert(0) += 1;
+                }
+
+                *count += 1;
+            }
+        }
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &K> {
+        self.groups.keys()
+    }
+
+    /// Words of a group used at least `min_count` times with their score and count, most distinctive first
+    pub fn distinctive(&self, key: &K, scoring: Scoring, min_count: usize) -> Vec<(String, f64, usize)> {
+        let Some(counts) = self.groups.get(key) else {
+            return vec![];
+        };
+
+        let group_size = counts.values().sum::<usize>() as f64;
+        let library_size = self.totals.values().sum::<usize>() as f64;
+
+        let mut scored: Vec<(String, f64, usize)> = counts
+            .iter()
+            .filter(|(_, count)| **count >= min_count)
+            .map(|(word, &count)| {
+                let total = self.totals[word] as f64;
+                let score = match scoring {
+                    // Monroe, Colaresi and Quinn, "Fightin' Words", the rest of the library is the other group
+                    Scoring::LogOdds => {
+                        let (ours, theirs) = (count as f64, total - count as f64);
+                        let rest_size = library_size - group_size;
+                        let odds = |used: f64, size: f64| (used + total) / (size + library_size - used - total);
+                        let delta = odds(ours, group_size).ln() - odds(theirs, rest_size).ln();
+
+                        delta / (1.0 / (ours + total) + 1.0 / (theirs + total)).sqrt()
+                    },
+                    Scoring::TfIdf => {
+                        count as f64 / group_size * (self.groups.len() as f64 / self.used_by[word] as f64).ln()
+                    },
+                };
+
+                (word.clone(), score, count)
+            })
+            .collect();
+
+        scored.sort_by(|(a_word, a_score, _), (b_word, b_score, _)| b_score.total_cmp(a_score).then_with(|| a_word.cmp(b_word)));
+
+        scored
+    }
+}
+
+/// Vocabulary and pace of the lyrics of several songs, every word counts including stopwords
+#[derive(Default)]
+pub struct LyricsStats {
+    pub songs: usize,
+    pub words: usize,
+    unique: HashSet<String>,
+    lines: usize,
+    /// Lines repeating an earlier line of the same song
+    repeated_lines: usize,
+    /// Words and seconds of songs with a known length
+    timed_words: usize,
+    seconds: u64,
+}
+
+impl LyricsStats {
+    pub fn add(&mut self, song: &SongData) {
+        let mut song_words = 0;
+        let mut song_lines: HashSet<String> = HashSet::new();
+
+        for line in song.lyrics.iter().flat_map(|phrase| phrase.lines()) {
+            let words = words(line);
+
+            if words.is_empty() {
+                continue;
+            }
+
+            song_words += words.len();
+            self.lines += 1;
+
+            if !song_lines.insert(words.join(" ")) {
+                self.repeated_lines += 1;
+            }
+
+            self.unique.extend(words);
+        }
+
+        self.songs += 1;
+        self.words += song_words;
+
+        if song.track_length > 0 {
+            self.timed_words += song_words;
+            self.seconds += song.track_length;
+        }
+    }
+
+    pub fn unique_words(&self) -> usize {
+        self.unique.len()
+    }
+
+    /// Unique words over all words, lower means a smaller vocabulary
+    pub fn type_token_ratio(&self) -> f64 {
+        ratio(self.unique.len() as f64, self.words as f64)
+    }
+
+    /// Words per line
+    pub fn line_length(&self) -> f64 {
+        ratio(self.words as f64, self.lines as f64)
+    }
+
+    pub fn words_per_minute(&self) -> f64 {
+        ratio(self.timed_words as f64 * 60.0, self.seconds as f64)
+    }
+
+    /// Share of lines that repeat an earlier line of their song, ex. a chorus
+    pub fn repetition(&self) -> f64 {
+        ratio(self.repeated_lines as f64, self.lines as f64)
+    }
+}
+
+fn ratio(part: f64, whole: f64) -> f64 {
+    match whole > 0.0 {
+        true => part / whole,
+        false => 0.0,
+    }
+}
+
+/// Every word of a line, lowercased
+fn words(line: &str) -> Vec<String> {
+    line.unicode_words().map(normalize).collect()
+}
+
+/// Lowercase with typographic apostrophes made plain, "Don’t" and "don't" are the same word
+fn normalize(word: &str) -> String {
+    word.to_lowercase().replace('\u{2019}', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LyricsStats, Scoring, Tokenizer, Vocabulary};
+    use crate::utils::{config::LyricsConfig, songs::SongData};
+
+    fn song(language: &str, lyrics: &str) -> SongData {
+        SongData { language: language.to_string(), lyrics: vec![lyrics.to_string()], ..Default::default() }
+    }
+
+    #[test]
+    fn keeps_accented_words_and_drops_stopwords() {
+        let tokenizer = Tokenizer::new(&LyricsConfig::default(), false);
+        let tokens = tokenizer.tokens(&song("spa", "Él está en la canción\nCorazón"), 1);
+
+        assert_eq!(tokens, vec!["canción", "corazón"]);
+    }
+
+    #[test]
+    fn builds_phrases_within_a_line() {
+        let tokenizer = Tokenizer::new(&LyricsConfig::default(), false);
+        let tokens = tokenizer.tokens(&song("eng", "Boulevard of broken dreams\nBroken dreams tonight"), 2);
+
+        assert_eq!(tokens, vec!["broken dreams", "broken dreams", "dreams tonight"]);
+    }
+
+    #[test]
+    fn stems_words() {
+        let tokenizer = Tokenizer::new(&LyricsConfig::default(), true);
+
+        assert_eq!(tokenizer.tokens(&song("eng", "loving loved"), 1), vec!["love", "love"]);
+    }
+
+    #[test]
+    fn scores_words_of_a_group_against_the_rest() {
+        let words = |text: &str| text.split(' ').map(String::from).collect::<Vec<_>>();
+        let mut vocabulary = Vocabulary::default();
+
+        vocabulary.add(vec!["punk", "rock"], &words("love riot riot love"));
+        vocabulary.add(vec!["pop"], &words("love love baby baby"));
+        vocabulary.add(vec!["folk"], &words("love river river"));
+
+        for scoring in [Scoring::LogOdds, Scoring::TfIdf] {
+            let scored = vocabulary.distinctive(&"punk", scoring, 1);
+
+            assert_eq!(scored.iter().map(|(word, _, _)| word.as_str()).collect::<Vec<_>>(), vec!["riot", "love"]);
+        }
+
+        // A song in two groups is counted once in the library
+        assert_eq!(vocabulary.totals["riot"], 2);
+    }
+
+    #[test]
+    fn measures_vocabulary_and_pace() {
+        let mut stats = LyricsStats::default();
+
+        stats.add(&SongData { track_length: 30, ..song("eng", "I walk alone\nI walk alone\n\nMy shadow's the only one") });
+
+        assert_eq!((stats.words, stats.unique_words()), (11, 8));
+        assert_eq!(stats.line_length(), 11.0 / 3.0);
+        assert_eq!(stats.words_per_minute(), 22.0);
+        assert_eq!(stats.repetition(), 1.0 / 3.0);
+
+        // A line shared with another song isn't a repeat
+        stats.add(&song("eng", "I walk alone\nOn the boulevard"));
+
+        assert_eq!(stats.repetition(), 1.0 / 5.0);
+    }
+}
@@ -1,7 +1,17 @@
+pub mod albums;
 pub mod cache;
+pub mod chart;
+pub mod config;
 pub mod covers;
 pub mod data;
 pub mod date;
+pub mod error;
 pub mod filters;
 pub mod kmeans;
+pub mod lyrics;
+pub mod output;
+pub mod query;
+pub mod scan;
 pub mod songs;
+pub mod store;
+pub mod walker;
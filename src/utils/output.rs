@@ -0,0 +1,176 @@
+use std::io::{self, Write};
+
+use prettytable::{format, Row, Table};
+use serde_json::{Map, Value};
+
+use super::error::MicError;
+
+/// How commands print their results, picked with the global --output flag
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    /// Array of objects keyed by column
+    Json,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Listings like playlist and wtp keep their plain lines unless another format is asked for
+    pub fn is_table(&self) -> bool {
+        *self == OutputFormat::Table
+    }
+}
+
+/// Rows of a command's result, each column has a stable key used by JSON, CSV and TSV
+pub struct Report {
+    columns: Vec<(String, String)>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Report {
+    /// Columns as (key, title), titles are only shown in tables and markdown
+    pub fn new(columns: &[(&str, &str)]) -> Report {
+        Report {
+            columns: columns.iter().map(|(key, title)| (key.to_string(), title.to_string())).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<Value>) {
+        self.rows.push(cells);
+    }
+
+    pub fn print(&self, format: OutputFormat) -> Result<(), MicError> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        match format {
+            OutputFormat::Table => self.table().print(&mut out).map(|_| ()),
+            OutputFormat::Json => self.json(&mut out),
+            OutputFormat::Csv => self.delimited(&mut out, b','),
+            OutputFormat::Tsv => self.delimited(&mut out, b'\t'),
+            OutputFormat::Markdown => self.markdown(&mut out),
+        }
+        .map_err(|e| MicError::io("<stdout>", e))
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_LINESEP);
+        table.add_row(Row::from(self.columns.iter().map(|(_, title)| title)));
+
+        for row in &self.rows {
+            table.add_row(Row::from(row.iter().map(cell_text)));
+        }
+
+        table
+    }
+
+    fn json(&self, out: &mut impl Write) -> io::Result<()> {
+        let objects: Vec<Map<String, Value>> = self
+            .rows
+            .iter()
+            .map(|row| self.columns.iter().map(|(key, _)| key.clone()).zip(row.iter().cloned()).collect())
+            .collect();
+
+        serde_json::to_writer_pretty(&mut *out, &objects)?;
+        writeln!(out)
+    }
+
+    fn delimited(&self, out: &mut impl Write, delimiter: u8) -> io::Result<()> {
+        let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(out);
+
+        writer.write_record(self.columns.iter().map(|(key, _)| key))?;
+
+        for row in &self.rows {
+            // Tabs and newlines can't be quoted in TSV, they become spaces
+            writer.write_record(row.iter().map(|cell| match delimiter {
+                b'\t' => cell_text(cell).replace(['\t', '\n', '\r'], " "),
+                _ => cell_text(cell),
+            }))?;
+        }
+
+        writer.flush()
+    }
+
+    fn markdown(&self, out: &mut impl Write) -> io::Result<()> {
+        let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+        writeln!(out, "{}", line(self.columns.iter().map(|(_, title)| title.replace('|', "\\|")).collect()))?;
+        writeln!(out, "{}", line(self.columns.iter().map(|_| "---".to_string()).collect()))?;
+
+        for row in &self.rows {
+            let cells = row.iter().map(|cell| cell_text(cell).replace('|', "\\|").replace('\n', "<br>")).collect();
+
+            writeln!(out, "{}", line(cells))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Strings are printed as is and lists joined, everything else as JSON
+fn cell_text(cell: &Value) -> String {
+    match cell {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(cell_text).collect::<Vec<_>>().join(", "),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Report;
+
+    fn report() -> Report {
+        let mut report = Report::new(&[("name", "Name"), ("genre", "Genre | Style"), ("songs", "Songs")]);
+
+        report.row(vec!["Shellac, \"live\"".into(), json!(["noise rock", "post-hardcore"]), 3.into()]);
+        report.row(vec!["Tab\tand\nnewline".into(), "a|b".into(), 12.into()]);
+        report
+    }
+
+    fn written(write: impl Fn(&Report, &mut Vec<u8>) -> std::io::Result<()>) -> String {
+        let mut out = vec![];
+
+        write(&report(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn quotes_csv_and_flattens_tsv() {
+        assert_eq!(
+            written(|r, out| r.delimited(out, b',')),
+            "name,genre,songs\n\"Shellac, \"\"live\"\"\",\"noise rock, post-hardcore\",3\n\"Tab\tand\nnewline\",a|b,12\n",
+        );
+        assert_eq!(
+            written(|r, out| r.delimited(out, b'\t')).lines().nth(2),
+            Some("Tab and newline\ta|b\t12"),
+        );
+    }
+
+    #[test]
+    fn escapes_markdown_cells() {
+        let markdown = written(|r, out| r.markdown(out));
+        let lines: Vec<&str> = markdown.lines().collect();
+
+        assert_eq!(lines[0], "| Name | Genre \\| Style | Songs |");
+        assert_eq!(lines[1], "| --- | --- | --- |");
+        assert_eq!(lines[3], "| Tab\tand<br>newline | a\\|b | 12 |");
+    }
+
+    #[test]
+    fn keeps_json_keys_in_column_order() {
+        let json = written(|r, out| r.json(out));
+        let (name, genre, songs) = (json.find("\"name\"").unwrap(), json.find("\"genre\"").unwrap(), json.find("\"songs\"").unwrap());
+
+        assert!(name < genre && genre < songs);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap()[1]["songs"], 12);
+    }
+}
@@ -0,0 +1,741 @@
+use chrono::{Datelike, Weekday};
+
+use super::{
+    date::DateField,
+    error::MicError,
+    filters::{fold, MatchMode, Predicate, TextMatcher},
+    songs::SongData,
+};
+
+/// A `--where` expression, ex. `genre:metal and (year>=1990 and year<2000) and not mood:happy`
+#[derive(Debug, Clone)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Term { field: Field, op: Op, value: Value, date_field: DateField },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Artist,
+    AlbumArtist,
+    Album,
+    Title,
+    Track,
+    Genre,
+    Mood,
+    Words,
+    Year,
+    Month,
+    Day,
+    Decade,
+    /// Day of the week of the release, 1 is Monday
+    Weekday,
+    Length,
+    Instrumental,
+    // Details
+    Label,
+    Language,
+    Catalog,
+    Publisher,
+    Copyright,
+    License,
+    Advisory,
+    Comment,
+    Description,
+    Work,
+    Movement,
+    Remixer,
+    // Credits
+    Arranger,
+    Composer,
+    Conductor,
+    Director,
+    Engineer,
+    Lyricist,
+    MixDj,
+    MixEngineer,
+    Musician,
+    Performer,
+    Producer,
+    Writer,
+    // Audio
+    Format,
+    Bitrate,
+    SampleRate,
+    BitDepth,
+    Channels,
+    Lossless,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// `:` substring for text, equality for numbers
+    Contains,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `~` regular expression, text only
+    Matches,
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Op::Contains => ":",
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Matches => "~",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// Folded, text matching ignores case and accents
+    Text(String),
+    /// Compiled value of a `~` term
+    Pattern(TextMatcher),
+    Number(i64),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    List,
+    Lyrics,
+    Number,
+    Bool,
+}
+
+/// A value songs are grouped under by `count by`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GroupKey {
+    Number(i64),
+    /// Days from Monday
+    Weekday(u8),
+    Text(String),
+    Bool(bool),
+}
+
+impl std::fmt::Display for GroupKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupKey::Number(number) => write!(f, "{}", number),
+            GroupKey::Weekday(day) => write!(f, "{}", Weekday::try_from(*day).map_err(|_| std::fmt::Error)?),
+            GroupKey::Text(text) => write!(f, "{}", text),
+            GroupKey::Bool(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+impl From<GroupKey> for serde_json::Value {
+    fn from(key: GroupKey) -> Self {
+        match key {
+            GroupKey::Number(number) => number.into(),
+            GroupKey::Bool(flag) => flag.into(),
+            other => other.to_string().into(),
+        }
+    }
+}
+
+impl Field {
+    /// Field for `count by`, names may be written with underscores ex. album_artist
+    pub fn parse_group(name: &str) -> Result<Field, MicError> {
+        match Field::parse(&name.replace(['_', '-'], "")) {
+            Some(Field::Words) => Err(MicError::query("lyrics can't be grouped, use count words")),
+            Some(field) => Ok(field),
+            None => Err(MicError::query(format!("unknown field \"{}\"", name))),
+        }
+    }
+
+    /// Values a song is counted under, one per entry of a list field and none for a missing tag
+    pub fn group_keys(&self, song: &SongData, date_field: DateField) -> Result<Vec<GroupKey>, MicError> {
+        Ok(match self.kind() {
+            Kind::Text => match self.text(song) {
+                "" => vec![],
+                text => vec![GroupKey::Text(text.to_string())],
+            },
+            Kind::List => self.list(song).iter().cloned().map(GroupKey::Text).collect(),
+            Kind::Bool => vec![GroupKey::Bool(self.flag(song))],
+            Kind::Number => match (self, self.number(song, date_field)?) {
+                (Field::Weekday, Some(day)) => vec![GroupKey::Weekday(day as u8 - 1)],
+                (_, number) => number.map(GroupKey::Number).into_iter().collect(),
+            },
+            Kind::Lyrics => vec![],
+        })
+    }
+
+    /// Canonical name, used as the key of the field's column in JSON and CSV output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Artist => "artist",
+            Field::AlbumArtist => "album_artist",
+            Field::Album => "album",
+            Field::Title => "title",
+            Field::Track => "track",
+            Field::Genre => "genre",
+            Field::Mood => "mood",
+            Field::Words => "words",
+            Field::Year => "year",
+            Field::Month => "month",
+            Field::Day => "day",
+            Field::Decade => "decade",
+            Field::Weekday => "weekday",
+            Field::Length => "length",
+            Field::Instrumental => "instrumental",
+            Field::Label => "label",
+            Field::Language => "language",
+            Field::Catalog => "catalog",
+            Field::Publisher => "publisher",
+            Field::Copyright => "copyright",
+            Field::License => "license",
+            Field::Advisory => "advisory",
+            Field::Comment => "comment",
+            Field::Description => "description",
+            Field::Work => "work",
+            Field::Movement => "movement",
+            Field::Remixer => "remixer",
+            Field::Arranger => "arranger",
+            Field::Composer => "composer",
+            Field::Conductor => "conductor",
+            Field::Director => "director",
+            Field::Engineer => "engineer",
+            Field::Lyricist => "lyricist",
+            Field::MixDj => "mix_dj",
+            Field::MixEngineer => "mix_engineer",
+            Field::Musician => "musician",
+            Field::Performer => "performer",
+            Field::Producer => "producer",
+            Field::Writer => "writer",
+            Field::Format => "format",
+            Field::Bitrate => "bitrate",
+            Field::SampleRate => "sample_rate",
+            Field::BitDepth => "bit_depth",
+            Field::Channels => "channels",
+            Field::Lossless => "lossless",
+        }
+    }
+
+    /// Column title in tables ex. "Album Artist"
+    pub fn title(&self) -> String {
+        self.name()
+            .split('_')
+            .map(|word| word[..1].to_uppercase() + &word[1..])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn parse(name: &str) -> Option<Field> {
+        Some(match name.to_lowercase().as_str() {
+            "artist" | "trackartist" => Field::Artist,
+            "albumartist" => Field::AlbumArtist,
+            "album" | "albumtitle" => Field::Album,
+            "title" | "tracktitle" => Field::Title,
+            "track" | "tracknumber" => Field::Track,
+            "genre" => Field::Genre,
+            "mood" => Field::Mood,
+            "words" | "lyrics" => Field::Words,
+            "year" => Field::Year,
+            "month" => Field::Month,
+            "day" => Field::Day,
+            "decade" => Field::Decade,
+            "weekday" => Field::Weekday,
+            "length" => Field::Length,
+            "instrumental" => Field::Instrumental,
+            "label" => Field::Label,
+            "language" => Field::Language,
+            "catalog" | "catalognumber" => Field::Catalog,
+            "publisher" => Field::Publisher,
+            "copyright" | "copyrightmessage" => Field::Copyright,
+            "license" => Field::License,
+            "advisory" | "parentaladvisory" => Field::Advisory,
+            "comment" => Field::Comment,
+            "description" => Field::Description,
+            "work" => Field::Work,
+            "movement" => Field::Movement,
+            "remixer" => Field::Remixer,
+            "arranger" => Field::Arranger,
+            "composer" => Field::Composer,
+            "conductor" => Field::Conductor,
+            "director" => Field::Director,
+            "engineer" => Field::Engineer,
+            "lyricist" => Field::Lyricist,
+            "mixdj" => Field::MixDj,
+            "mixengineer" => Field::MixEngineer,
+            "musician" | "musiciancredits" => Field::Musician,
+            "performer" => Field::Performer,
+            "producer" => Field::Producer,
+            "writer" => Field::Writer,
+            "format" => Field::Format,
+            "bitrate" => Field::Bitrate,
+            "samplerate" => Field::SampleRate,
+            "bitdepth" => Field::BitDepth,
+            "channels" => Field::Channels,
+            "lossless" => Field::Lossless,
+            _ => return None,
+        })
+    }
+
+    fn kind(&self) -> Kind {
+        match self {
+            Field::Artist | Field::AlbumArtist | Field::Album | Field::Title | Field::Track => Kind::Text,
+            Field::Label | Field::Language | Field::Catalog | Field::Publisher | Field::Copyright => Kind::Text,
+            Field::License | Field::Advisory | Field::Comment | Field::Description => Kind::Text,
+            Field::Work | Field::Movement | Field::Remixer | Field::Format => Kind::Text,
+            Field::Genre | Field::Mood => Kind::List,
+            Field::Arranger | Field::Composer | Field::Conductor | Field::Director | Field::Engineer => Kind::List,
+            Field::Lyricist | Field::MixDj | Field::MixEngineer | Field::Musician | Field::Performer => Kind::List,
+            Field::Producer | Field::Writer => Kind::List,
+            Field::Words => Kind::Lyrics,
+            Field::Year | Field::Month | Field::Day | Field::Decade | Field::Weekday | Field::Length => Kind::Number,
+            Field::Bitrate | Field::SampleRate | Field::BitDepth | Field::Channels => Kind::Number,
+            Field::Instrumental | Field::Lossless => Kind::Bool,
+        }
+    }
+
+    fn text<'a>(&self, song: &'a SongData) -> &'a str {
+        match self {
+            Field::Artist => &song.track_artist,
+            Field::AlbumArtist => &song.album_artist,
+            Field::Album => &song.album_title,
+            Field::Title => &song.track_title,
+            Field::Label => &song.label,
+            Field::Language => &song.language,
+            Field::Catalog => &song.catalog_number,
+            Field::Publisher => &song.publisher,
+            Field::Copyright => &song.copyright_message,
+            Field::License => &song.license,
+            Field::Advisory => &song.parental_advisory,
+            Field::Comment => &song.comment,
+            Field::Description => &song.description,
+            Field::Work => &song.work,
+            Field::Movement => &song.movement,
+            Field::Remixer => &song.remixer,
+            Field::Format => &song.format,
+            _ => &song.track_number,
+        }
+    }
+
+    fn list<'a>(&self, song: &'a SongData) -> &'a [String] {
+        match self {
+            Field::Genre => &song.genre,
+            Field::Mood => &song.mood,
+            Field::Arranger => &song.arranger,
+            Field::Composer => &song.composer,
+            Field::Conductor => &song.conductor,
+            Field::Director => &song.director,
+            Field::Engineer => &song.engineer,
+            Field::Lyricist => &song.lyricist,
+            Field::MixDj => &song.mix_dj,
+            Field::MixEngineer => &song.mix_engineer,
+            Field::Musician => &song.musician_credits,
+            Field::Performer => &song.performer,
+            Field::Producer => &song.producer,
+            Field::Writer => &song.writer,
+            _ => &song.lyrics,
+        }
+    }
+
+    fn flag(&self, song: &SongData) -> bool {
+        match self {
+            Field::Lossless => song.is_lossless(),
+            _ => song.lyrics.is_empty(),
+        }
+    }
+
+    /// None when the date is missing or too coarse for the field, ex. the month of "1994", or the property is unknown
+    fn number(&self, song: &SongData, date_field: DateField) -> Result<Option<i64>, MicError> {
+        // Audio properties the file doesn't report are 0, they never match
+        let known = |value: i64| Ok(Some(value).filter(|&v| v > 0));
+
+        match self {
+            Field::Length => return Ok(Some(song.track_length as i64)),
+            Field::Bitrate => return known(song.bitrate as i64),
+            Field::SampleRate => return known(song.sample_rate as i64),
+            Field::BitDepth => return known(song.bit_depth as i64),
+            Field::Channels => return known(song.channels as i64),
+            _ => {},
+        }
+
+        let Some(date) = song.date(date_field)? else {
+            return Ok(None);
+        };
+
+        Ok(match self {
+            Field::Month => date.month.map(i64::from),
+            Field::Day => date.day.map(i64::from),
+            Field::Decade => Some(date.decade() as i64),
+            Field::Weekday => date.full().map(|d| d.weekday().number_from_monday() as i64),
+            _ => Some(date.year as i64),
+        })
+    }
+}
+
+impl Query {
+    /// Date fields like year and month read the tag chosen by `date_field`
+    pub fn parse(input: &str, date_field: DateField) -> Result<Query, MicError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0, date_field };
+        let query = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(query),
+            Some(token) => Err(MicError::query(format!("unexpected {}", token))),
+        }
+    }
+}
+
+impl Predicate for Query {
+    /// Fails when a date the expression needs can't be parsed
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        match self {
+            // A side that can't be evaluated only fails the match when the other side can't decide it
+            Query::And(left, right) => match left.matches(song) {
+                Ok(matches) => Ok(matches && right.matches(song)?),
+                Err(e) => match right.matches(song)? {
+                    false => Ok(false),
+                    true => Err(e),
+                },
+            },
+            Query::Or(left, right) => match left.matches(song) {
+                Ok(matches) => Ok(matches || right.matches(song)?),
+                Err(e) => match right.matches(song)? {
+                    true => Ok(true),
+                    false => Err(e),
+                },
+            },
+            Query::Not(query) => Ok(!query.matches(song)?),
+            Query::Term { field, op, value, date_field } => match (field.kind(), value) {
+                (Kind::Text, Value::Text(needle)) => Ok(compare_text(&fold(field.text(song)), *op, needle)),
+                (Kind::Text, Value::Pattern(pattern)) => Ok(pattern.matches(field.text(song))),
+                (Kind::List, Value::Pattern(pattern)) => Ok(pattern.matches_any(field.list(song))),
+                (Kind::Lyrics, Value::Pattern(pattern)) => Ok(pattern.matches_any(&song.lyrics)),
+                (Kind::List, Value::Text(needle)) => {
+                    let any = |op| field.list(song).iter().any(|item| compare_text(&fold(item), op, needle));
+
+                    Ok(match op {
+                        Op::Ne => !any(Op::Eq),
+                        _ => any(*op),
+                    })
+                },
+                (Kind::Lyrics, Value::Text(needle)) => {
+                    Ok(song.lyrics.iter().any(|phrase| fold(phrase).contains(needle.as_str())))
+                },
+                (Kind::Number, Value::Number(number)) => {
+                    Ok(field.number(song, *date_field)?.is_some_and(|n| compare(&n, *op, number)))
+                },
+                (Kind::Bool, Value::Bool(expected)) => Ok(field.flag(song) == *expected),
+                _ => Ok(false),
+            },
+        }
+    }
+}
+
+fn compare_text(haystack: &str, op: Op, needle: &str) -> bool {
+    match op {
+        Op::Contains => haystack.contains(needle),
+        _ => compare(&haystack, op, &needle),
+    }
+}
+
+fn compare<T: PartialOrd>(left: &T, op: Op, right: &T) -> bool {
+    match op {
+        Op::Contains | Op::Eq | Op::Matches => left == right,
+        Op::Ne => left != right,
+        Op::Lt => left < right,
+        Op::Le => left <= right,
+        Op::Gt => left > right,
+        Op::Ge => left >= right,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "\"(\""),
+            Token::Close => write!(f, "\")\""),
+            Token::Op(op) => write!(f, "\"{}\"", op),
+            Token::Word(word) => write!(f, "\"{}\"", word),
+            Token::Quoted(word) => write!(f, "\"{}\"", word),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, MicError> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {},
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            ':' => tokens.push(Token::Op(Op::Contains)),
+            '=' => tokens.push(Token::Op(Op::Eq)),
+            '~' => tokens.push(Token::Op(Op::Matches)),
+            '!' | '<' | '>' => {
+                let or_equal = chars.next_if_eq(&'=').is_some();
+
+                tokens.push(Token::Op(match (c, or_equal) {
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => return Err(MicError::query("\"!\" must be followed by \"=\"")),
+                }));
+            },
+            '"' | '\'' => {
+                let mut word = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(next) => word.push(next),
+                        None => return Err(MicError::query(format!("missing closing {}", c))),
+                    }
+                }
+
+                tokens.push(Token::Quoted(word));
+            },
+            _ => {
+                let mut word = c.to_string();
+
+                while let Some(next) = chars.next_if(|n| !n.is_whitespace() && !"()\"':=~!<>".contains(*n)) {
+                    word.push(next);
+                }
+
+                tokens.push(Token::Word(word));
+            },
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    date_field: DateField,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, MicError> {
+        let mut query = self.and()?;
+
+        while self.keyword("or") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, MicError> {
+        let mut query = self.unary()?;
+
+        while self.keyword("and") {
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+
+        Ok(query)
+    }
+
+    fn unary(&mut self) -> Result<Query, MicError> {
+        if self.keyword("not") {
+            return Ok(Query::Not(Box::new(self.unary()?)));
+        }
+
+        match self.next() {
+            Some(Token::Open) => {
+                let query = self.or()?;
+
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(MicError::query("missing closing \")\"")),
+                }
+            },
+            Some(Token::Word(name)) => self.term(&name),
+            Some(token) => Err(MicError::query(format!("expected a field, found {}", token))),
+            None => Err(MicError::query("expression ends too early")),
+        }
+    }
+
+    fn term(&mut self, name: &str) -> Result<Query, MicError> {
+        let field = Field::parse(name).ok_or_else(|| MicError::query(format!("unknown field \"{}\"", name)))?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(MicError::query(format!("expected an operator after \"{}\"", name))),
+        };
+        let raw = match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => value,
+            _ => return Err(MicError::query(format!("expected a value after \"{}\"", name))),
+        };
+
+        let value = match field.kind() {
+            Kind::Text | Kind::List | Kind::Lyrics if op == Op::Matches => {
+                Value::Pattern(TextMatcher::new(&raw, MatchMode::Regex)?)
+            },
+            Kind::Number => Value::Number(
+                raw.parse().map_err(|_| MicError::query(format!("{} needs a number, found \"{}\"", name, raw)))?,
+            ),
+            Kind::Bool => Value::Bool(match raw.to_lowercase().as_str() {
+                "true" | "yes" => true,
+                "false" | "no" => false,
+                _ => return Err(MicError::query(format!("{} needs true or false, found \"{}\"", name, raw))),
+            }),
+            _ => Value::Text(fold(&raw)),
+        };
+
+        let allowed = match field.kind() {
+            Kind::Number => op != Op::Matches,
+            Kind::Text | Kind::List => matches!(op, Op::Contains | Op::Eq | Op::Ne | Op::Matches),
+            Kind::Lyrics => matches!(op, Op::Contains | Op::Eq | Op::Matches),
+            Kind::Bool => matches!(op, Op::Contains | Op::Eq),
+        };
+
+        if !allowed {
+            return Err(MicError::query(format!("\"{}\" can't be used with {}", op, name)));
+        }
+
+        Ok(Query::Term { field, op, value, date_field: self.date_field })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, GroupKey, Query};
+    use crate::utils::{date::DateField, error::MicError, filters::Predicate, songs::SongData};
+
+    fn song(genre: &str, mood: &str, date: &str) -> SongData {
+        SongData {
+            genre: vec![genre.to_string()],
+            mood: vec![mood.to_string()],
+            recording_date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_nested_expression() {
+        let query = Query::parse("genre:metal and (year>=1990 and year<2000) and not mood:happy", DateField::Recording).unwrap();
+
+        assert!(query.matches(&song("Metal", "Dark", "1994-10-24")).unwrap());
+        assert!(!query.matches(&song("Metal", "Happy", "1994-10-24")).unwrap());
+        assert!(!query.matches(&song("Metal", "Dark", "2004-09-21")).unwrap());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let query = Query::parse("genre=punk or genre=metal and year<1990", DateField::Recording).unwrap();
+
+        assert!(query.matches(&song("Punk", "", "2004-09-21")).unwrap());
+        assert!(!query.matches(&song("Metal", "", "2004-09-21")).unwrap());
+    }
+
+    #[test]
+    fn ignores_case_and_accents() {
+        let bjork = SongData { track_artist: "Björk".to_string(), ..Default::default() };
+
+        assert!(Query::parse("artist=BJORK", DateField::Recording).unwrap().matches(&bjork).unwrap());
+        assert!(Query::parse("artist~^bj.rk$", DateField::Recording).unwrap().matches(&bjork).unwrap());
+    }
+
+    #[test]
+    fn matches_credits_and_details() {
+        let shellac = SongData {
+            producer: vec!["Steve Albini".to_string()],
+            label: "Touch and Go".to_string(),
+            ..Default::default()
+        };
+
+        assert!(Query::parse("producer=\"steve albini\" and label:touch", DateField::Recording).unwrap().matches(&shellac).unwrap());
+        assert!(!Query::parse("composer:albini", DateField::Recording).unwrap().matches(&shellac).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        for input in ["genre:", "year>=abc", "genre<metal", "artist~(", "year~19", "(genre:metal", "colour:red", "genre:metal mood:dark"] {
+            assert!(Query::parse(input, DateField::Recording).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_group_fields_with_separators() {
+        for name in ["album_artist", "album-artist", "AlbumArtist"] {
+            assert_eq!(Field::parse_group(name).unwrap(), Field::AlbumArtist);
+        }
+
+        assert!(Field::parse_group("words").is_err());
+        assert!(Field::parse_group("colour").is_err());
+    }
+
+    #[test]
+    fn groups_every_value_of_a_list() {
+        let song = SongData { genre: vec!["Noise Rock".to_string(), "Punk".to_string()], ..song("", "", "1994-10-24") };
+        let text = |t: &str| GroupKey::Text(t.to_string());
+
+        assert_eq!(Field::Genre.group_keys(&song, DateField::Recording).unwrap(), vec![text("Noise Rock"), text("Punk")]);
+        assert_eq!(Field::Decade.group_keys(&song, DateField::Recording).unwrap(), vec![GroupKey::Number(1990)]);
+        assert_eq!(Field::Weekday.group_keys(&song, DateField::Recording).unwrap(), vec![GroupKey::Weekday(0)]);
+        assert_eq!(GroupKey::Weekday(0).to_string(), "Mon");
+    }
+
+    #[test]
+    fn skips_missing_tags() {
+        let song = song("", "", "1994");
+
+        assert!(Field::Label.group_keys(&song, DateField::Recording).unwrap().is_empty());
+        assert!(Field::Producer.group_keys(&song, DateField::Recording).unwrap().is_empty());
+        // The weekday needs a full date
+        assert!(Field::Weekday.group_keys(&song, DateField::Recording).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fails_on_unreadable_dates() {
+        let result = Field::Year.group_keys(&song("", "", "someday"), DateField::Recording);
+
+        assert!(matches!(result, Err(MicError::Date { .. })));
+        assert_eq!(result.unwrap_err().in_file("a.flac").to_string(), "a.flac: \"someday\" is not a valid date");
+    }
+}
@@ -0,0 +1,107 @@
+use std::{collections::HashMap, io::IsTerminal};
+
+use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+
+use super::{
+    cache::{CacheData, Fingerprint},
+    config::Config,
+    error::MicError,
+};
+
+/// A file that isn't in the cache yet, or changed since it was cached
+pub struct Pending {
+    pub filename: String,
+    pub fingerprint: Fingerprint,
+}
+
+/// Fingerprints of the files, files that can't be read are skipped
+pub fn fingerprint_files(config: &Config, filenames: Vec<String>) -> (Vec<Pending>, Vec<MicError>) {
+    let mut files: Vec<Pending> = vec![];
+    let mut skipped: Vec<MicError> = vec![];
+
+    for filename in filenames {
+        match Fingerprint::of(filename.as_ref(), config.cache.hash) {
+            Ok(fingerprint) => files.push(Pending { filename, fingerprint }),
+            Err(e) => skipped.push(e),
+        }
+    }
+
+    (files, skipped)
+}
+
+/// Whether the cached entry of a file can be used as is, for `load_cache_where`
+pub fn is_unchanged(files: &[Pending]) -> impl Fn(&str, &Fingerprint) -> bool + '_ {
+    let current: HashMap<&str, &Fingerprint> = files.iter().map(|f| (f.filename.as_str(), &f.fingerprint)).collect();
+
+    move |filename, cached| current.get(filename).is_some_and(|current| cached.is_fresh(current))
+}
+
+/// Files the cache can't answer, they aren't cached yet or changed since
+pub fn pending_files<T, F>(cache: &CacheData<T>, files: Vec<Pending>, is_usable: F) -> Vec<Pending>
+where
+    F: Fn(&T) -> bool,
+{
+    files
+        .into_iter()
+        .filter(|file| cache.get_fresh(&file.filename, &file.fingerprint).filter(|value| is_usable(value)).is_none())
+        .collect()
+}
+
+/// Reads every pending file in parallel, nothing is shared between the workers
+pub fn read_parallel<T, F>(config: &Config, label: &str, pending: Vec<Pending>, read: F) -> Vec<(Pending, Result<T, MicError>)>
+where
+    T: Send,
+    F: Fn(&str) -> Result<T, MicError> + Sync,
+{
+    let progress = progress_bar(config, label, pending.len());
+    let run = || {
+        pending
+            .into_par_iter()
+            .progress_with(progress.clone())
+            .map(|file| {
+                let result = read(&file.filename);
+                (file, result)
+            })
+            .collect()
+    };
+
+    let results = match rayon::ThreadPoolBuilder::new().num_threads(config.scan.threads).build() {
+        Ok(pool) => pool.install(run),
+        Err(_) => run(),
+    };
+
+    progress.finish_and_clear();
+
+    results
+}
+
+/// Writes the results of `read_parallel` into the cache, returns the files that failed
+pub fn merge_results<T>(cache: &mut CacheData<T>, results: Vec<(Pending, Result<T, MicError>)>) -> Vec<MicError> {
+    let mut skipped: Vec<MicError> = vec![];
+
+    for (file, result) in results {
+        match result {
+            Ok(value) => cache.insert(file.filename, file.fingerprint, value),
+            Err(e) => {
+                cache.remove(&file.filename);
+                skipped.push(e);
+            },
+        }
+    }
+
+    skipped
+}
+
+/// Only shown on a terminal, and only when enough files need reading to be worth it
+fn progress_bar(config: &Config, label: &str, len: usize) -> ProgressBar {
+    if len < config.scan.progress_threshold || !std::io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+
+    let style = ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} ({eta})")
+        .unwrap_or_else(|_| ProgressStyle::default_bar())
+        .progress_chars("=> ");
+
+    ProgressBar::new(len as u64).with_style(style).with_message(label.to_string())
+}
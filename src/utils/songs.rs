@@ -1,29 +1,31 @@
-use std::{
-    collections::hash_map::Entry,
-    error::Error,
-};
+use std::{error::Error, path::PathBuf};
 
-use chrono::Datelike;
-use dirs;
-use glob::{glob_with, MatchOptions};
-use lofty::{probe::Probe, tag::Tag, prelude::{AudioFile, ItemKey, TaggedFileExt}};
+use lofty::{file::FileType, probe::Probe, properties::FileProperties, tag::Tag, prelude::{AudioFile, ItemKey, TaggedFileExt}};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    cache::{load_cache_file, save_cache_file},
-    data::{string_clean, string_to_vec},
-    date::parse_string_to_datetime,
+    cache::{load_cache_where, save_cache, CacheRecord},
+    config::Config,
+    data::string_to_vec,
+    date::{DateField, PartialDate},
+    error::{report_skipped, MicError},
     filters::{
         match_lyrics_contain_words,
         equals_same_value, 
         match_decade, 
         match_no_lyrics, 
         match_current_week, 
-        contains_list_of_strings, match_items_left
+        match_items_left,
+        match_text,
+        match_any_text,
+        Predicate,
+        TextMatcher,
     },
+    scan::{fingerprint_files, is_unchanged, merge_results, pending_files, read_parallel},
+    walker::walk_library,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SongData {
     // Track Info
     pub album_artist: String,
@@ -41,6 +43,8 @@ pub struct SongData {
     pub track_number: String,
     pub track_title: String,
     pub track_total: String,
+    #[serde(default)]
+    pub disc_number: String,
     pub filename: String,
     pub dir: String,
     pub release_date: String,
@@ -71,185 +75,213 @@ pub struct SongData {
     pub remixer: String,
     pub work: String,
     pub writer: Vec<String>,
+
+    // Audio Properties
+    /// Codec or container ex. "flac", "opus", "mp3"
+    #[serde(default)]
+    pub format: String,
+    /// Kbps, 0 when unknown
+    #[serde(default)]
+    pub bitrate: u32,
+    /// Hz, 0 when unknown
+    #[serde(default)]
+    pub sample_rate: u32,
+    /// 0 when unknown or not meaningful for the codec
+    #[serde(default)]
+    pub bit_depth: u8,
+    #[serde(default)]
+    pub channels: u8,
 }
 
+/// Formats that keep the audio as is
+const LOSSLESS_FORMATS: [&str; 6] = ["flac", "alac", "wav", "aiff", "ape", "wavpack"];
+
 #[derive(Default)]
 pub struct SongDataFilter {
     pub day: Option<u32>,
     pub month: Option<u32>,
     pub year: Option<i32>,
-    pub artist: Option<String>,
-    pub album: Option<String>,
-    pub genre: Option<String>,
-    pub moods: Option<String>,
-    pub words: Option<String>,
+    pub artist: Option<TextMatcher>,
+    pub album: Option<TextMatcher>,
+    pub genre: Option<TextMatcher>,
+    pub moods: Option<TextMatcher>,
+    pub words: Option<TextMatcher>,
     pub decade: Option<u16>,
-    pub track: Option<String>,
+    pub track: Option<TextMatcher>,
     pub week: bool,
     pub left: bool,
     pub instrumental: bool,
+    pub date_field: DateField,
 }
 
-impl SongDataFilter {
-    pub fn filter(&self, songs: Vec<SongData>) -> Vec<SongData> {
-        songs.into_iter().filter(|song| self.matches(song)).collect()
-    }
-
-    pub fn matches(&self, song: &SongData) -> bool {
-        let dt = parse_string_to_datetime(&song.recording_date)
-            .expect("Something went wrong with date parsing");
-
-        let mut matches: Vec<bool> = vec![];
-
-        // Matching Genres & Moods
-        matches.push(contains_list_of_strings(&self.genre, &song.genre));
-        matches.push(contains_list_of_strings(&self.moods, &song.mood));
-
-        // Matching Genres
-        matches.push(match_lyrics_contain_words(&self.words, &song.lyrics));
-
-        // Matching by release params
-        matches.push(equals_same_value::<i32>(&self.year, &dt.year()));
-        matches.push(equals_same_value::<u32>(&self.month, &dt.month()));
-        matches.push(equals_same_value::<u32>(&self.day, &dt.day()));
-        // Matching by Decade
-        matches.push(match_decade(dt, &self.decade));
+impl SongData {
+    /// Date tag chosen by `field`, partial dates like "1994" are allowed
+    /// None when the tag is empty, only a date that can't be read is an error
+    pub fn date(&self, field: DateField) -> Result<Option<PartialDate>, MicError> {
+        let tag = match field {
+            DateField::Recording => &self.recording_date,
+            DateField::Release => &self.release_date,
+        };
 
-        // Matching by Track info
-        matches.push(equals_same_value::<String>(&self.artist, &song.track_artist));
-        matches.push(equals_same_value::<String>(&self.album, &song.album_title));
-        matches.push(equals_same_value::<String>(&self.track, &song.track_number));
+        match tag.trim() {
+            "" => Ok(None),
+            tag => PartialDate::parse(tag).map(Some),
+        }
+    }
 
+    pub fn is_lossless(&self) -> bool {
+        LOSSLESS_FORMATS.contains(&self.format.as_str())
+    }
 
-        // Matching by Current Week
-        if self.week {
-            matches.push(match_current_week(&song.recording_date))
-        }
+    /// Every name in the production credits, whatever the role
+    pub fn credits(&self) -> impl Iterator<Item = &String> {
+        [
+            &self.arranger,
+            &self.composer,
+            &self.conductor,
+            &self.director,
+            &self.engineer,
+            &self.lyricist,
+            &self.mix_dj,
+            &self.mix_engineer,
+            &self.musician_credits,
+            &self.performer,
+            &self.producer,
+            &self.writer,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(std::iter::once(&self.remixer).filter(|remixer| !remixer.is_empty()))
+    }
+}
 
-        // Matching by Current Week
-        if self.left {
-            matches.push(match_items_left(&song.recording_date))
+impl Predicate for SongDataFilter {
+    fn matches(&self, song: &SongData) -> Result<bool, MicError> {
+        let mut matches: Vec<bool> = vec![
+            // Matching Genres & Moods
+            match_any_text(&self.genre, &song.genre),
+            match_any_text(&self.moods, &song.mood),
+            // Matching Genres
+            match_lyrics_contain_words(&self.words, &song.lyrics),
+            // Matching by Track info
+            match_text(&self.artist, &song.track_artist),
+            match_text(&self.album, &song.album_title),
+            match_text(&self.track, &song.track_number),
+        ];
+
+        // Only parse the date when a filter needs it, a month or day never matches a date without one
+        if self.has_date_filter() {
+            match song.date(self.date_field)? {
+                Some(date) => {
+                    // Matching by release params
+                    matches.push(equals_same_value::<i32>(&self.year, &date.year));
+                    matches.push(self.month.is_none() || self.month == date.month);
+                    matches.push(self.day.is_none() || self.day == date.day);
+                    // Matching by Decade
+                    matches.push(match_decade(&date, &self.decade));
+                    // Matching by Current Week
+                    matches.push(!self.week || match_current_week(&date));
+                    // Matching by what's left of the year
+                    matches.push(!self.left || match_items_left(&date));
+                },
+                None => matches.push(false),
+            }
         }
 
         if self.instrumental {
             matches.push(match_no_lyrics(&song.lyrics));
         }
 
-        matches.iter().all(|&check| check)
+        Ok(matches.iter().all(|&check| check))
     }
 }
 
-const CACHE_FILE_NAME: &str = "songs_cache.json";
-
-pub fn phrases_to_words(phrases: String) -> Vec<String> {
-    let conjunctions = [
-        "and",
-        "but",
-        "or",
-        "nor",
-        "for",
-        "so",
-        "yet",
-        "because",
-        "although",
-        "though",
-        "even though",
-        "while",
-        "if",
-        "unless",
-        "until",
-        "after",
-        "before",
-        "since",
-        "when",
-        "whenever",
-        "where",
-        "wherever",
-        "whether",
-        "as",
-        "than",
-        "that",
-        "whether",
-        "now",
-    ];
-
-    string_clean(phrases)
-        .split_whitespace()
-        .filter(|w| !conjunctions.contains(w) && w.len() > 4)
-        .map(|s| s.trim_matches(|c: char| !c.is_ascii_alphabetic()).to_string())
-        .collect()
+impl SongDataFilter {
+    fn has_date_filter(&self) -> bool {
+        self.year.is_some() || self.month.is_some() || self.day.is_some() || self.decade.is_some() || self.week || self.left
+    }
 }
 
-pub enum ListMode {
-    Songs,
-    Albums
+pub const CACHE_FILE_NAME: &str = "songs_cache.json";
+
+impl CacheRecord for SongData {
+    const TABLE: &'static str = "songs";
+    const JSON_NAME: &'static str = CACHE_FILE_NAME;
+    const COLUMNS: &'static [&'static str] =
+        &["album_artist", "album_title", "track_artist", "genre", "year", "dir"];
+
+    fn indexed_columns(&self) -> Vec<String> {
+        vec![
+            self.album_artist.clone(),
+            self.album_title.clone(),
+            self.track_artist.clone(),
+            self.genre.join(";"),
+            self.recording_date.chars().take(4).collect(),
+            self.dir.clone(),
+        ]
+    }
 }
 
-pub fn get_songs() -> Result<Vec<SongData>, Box<dyn Error>> {
-    let music_dir = dirs::audio_dir().unwrap();
-    let pattern = format!("{}/*/*/*[.flac,.mp3,.wav,.opus]", music_dir.to_string_lossy());
-
-    Ok(songs_list(pattern, ListMode::Songs)?)
+pub fn get_songs(config: &Config) -> Result<Vec<SongData>, Box<dyn Error>> {
+    songs_list(config, walk_library(config))
 }
 
-pub fn get_albums() -> Result<Vec<SongData>, Box<dyn Error>> {
-    let music_dir = dirs::audio_dir().unwrap();
-    let pattern = format!("{}/*/*/01*[.flac,.mp3,.wav,.opus]", music_dir.to_string_lossy());
+/// Collects a list of songs from the given files, re-reading tags of files changed since cached
+pub fn songs_list(config: &Config, paths: Vec<PathBuf>) -> Result<Vec<SongData>, Box<dyn Error>> {
+    let filenames = paths.iter().map(|path| path.display().to_string()).collect();
+    let (files, mut skipped) = fingerprint_files(config, filenames);
+    let mut cache_file = load_cache_where::<SongData, _>(is_unchanged(&files))?;
 
-    Ok(songs_list(pattern, ListMode::Albums)?)
-}
+    let pending = pending_files(&cache_file, files, |_| true);
+    let results = read_parallel(config, "Reading tags", pending, load_song_tag);
+    skipped.extend(merge_results(&mut cache_file, results));
 
-/// Collects a list of songs by pattern
-pub fn songs_list(music_pattern: String, mode: ListMode) -> Result<Vec<SongData>, Box<dyn Error>> {
-    let globs = glob_with(
-        &music_pattern,
-        MatchOptions {
-            case_sensitive: false,
-            require_literal_separator: false,
-            require_literal_leading_dot: false,
-        },
-    )?;
-    let mut cache_file = load_cache_file::<SongData>(CACHE_FILE_NAME)?;
-    let paths: Vec<_> = globs.filter_map(|entry| entry.ok()).collect();
-
-    for path in paths {
-        let filename = path.display().to_string();
-
-        match cache_file.data.entry(filename.clone()) {
-            Entry::Occupied(o) => o.into_mut(),
-            Entry::Vacant(v) => v.insert(load_song_tag(&filename)),
-        };
-    }
+    cache_file.prune_missing();
+    save_cache::<SongData>(&cache_file)?;
+    report_skipped(&skipped);
 
-    save_cache_file::<SongData>(&cache_file, CACHE_FILE_NAME)?;
+    let map_to_vec: Vec<SongData> = cache_file.values().cloned().collect();
 
-    let map_to_vec: Vec<SongData> = {
-        let cache_file_ref = cache_file;
-        cache_file_ref.data.values().cloned().collect()
-    };
+    Ok(map_to_vec)
+}
 
-    Ok(match mode {
-        ListMode::Albums => map_to_vec.iter().filter(|s| s.filename.contains("/01")).map(|s| s.clone()).collect(),
-        ListMode::Songs => map_to_vec
-    })
+/// Short lowercase name of the codec, MP4 files only hold a bit depth when they're ALAC
+fn format_name(file_type: &FileType, properties: &FileProperties) -> String {
+    match file_type {
+        FileType::Aac => "aac",
+        FileType::Aiff => "aiff",
+        FileType::Ape => "ape",
+        FileType::Flac => "flac",
+        FileType::Mpeg => "mp3",
+        FileType::Mp4 if properties.bit_depth().is_some() => "alac",
+        FileType::Mp4 => "aac",
+        FileType::Mpc => "mpc",
+        FileType::Opus => "opus",
+        FileType::Vorbis => "vorbis",
+        FileType::Speex => "speex",
+        FileType::Wav => "wav",
+        FileType::WavPack => "wavpack",
+        FileType::Custom(name) => return name.to_lowercase(),
+        _ => "",
+    }
+    .to_string()
 }
 
 fn get_tag(tag: &Tag, key: &ItemKey) -> String {
-    match tag.get_string(key) {
-        Some(value) => value,
-        None => ""
-    }.to_string()
+    tag.get_string(key).unwrap_or_default().to_string()
 }
 
-pub fn load_song_tag(filename: &String) -> SongData {
+pub fn load_song_tag(filename: &str) -> Result<SongData, MicError> {
+    std::fs::metadata(filename).map_err(|e| MicError::io(filename, e))?;
+
     let tagged_file = Probe::open(filename)
-        .expect("ERROR: Bad path provided!")
+        .map_err(|e| MicError::tag(filename, e))?
         .read()
-        .expect("ERROR: Failed to read file!");
+        .map_err(|e| MicError::tag(filename, e))?;
 
     let tag = match tagged_file.primary_tag() {
         Some(primary) => primary,
-        None => tagged_file.first_tag().expect("ERROR: No tags found!"),
+        None => tagged_file.first_tag().ok_or_else(|| MicError::tag(filename, "no tags found"))?,
     };
 
     let album_artist = get_tag(tag, &ItemKey::AlbumArtist);
@@ -284,18 +316,25 @@ pub fn load_song_tag(filename: &String) -> SongData {
     let release_date = get_tag(tag, &ItemKey::OriginalReleaseDate);
     let recording_date = get_tag(tag, &ItemKey::RecordingDate);
     let remixer = get_tag(tag, &ItemKey::Remixer);
-    let track_length = tagged_file.properties().duration().as_secs();
+    let properties = tagged_file.properties();
+    let track_length = properties.duration().as_secs();
+    let format = format_name(&tagged_file.file_type(), properties);
+    let bitrate = properties.audio_bitrate().or(properties.overall_bitrate()).unwrap_or_default();
+    let sample_rate = properties.sample_rate().unwrap_or_default();
+    let bit_depth = properties.bit_depth().unwrap_or_default();
+    let channels = properties.channels().unwrap_or_default();
     let track_artist = get_tag(tag, &ItemKey::TrackArtist);
     let track_number = get_tag(tag, &ItemKey::TrackNumber);
     let track_title = get_tag(tag, &ItemKey::TrackTitle);
     let track_total = get_tag(tag, &ItemKey::TrackTotal);
+    let disc_number = get_tag(tag, &ItemKey::DiscNumber);
     let work = get_tag(tag, &ItemKey::Work);
     let writer = string_to_vec(get_tag(tag, &ItemKey::Writer), ",");
     let mut filename_split: Vec<&str> = filename.split('/').collect();
-    filename_split.pop().unwrap();
+    filename_split.pop();
     let dir = filename_split.join("/");
 
-    SongData {
+    Ok(SongData {
         album_artist,
         album_title,
         arranger,
@@ -333,11 +372,17 @@ pub fn load_song_tag(filename: &String) -> SongData {
         track_number,
         track_title,
         track_total,
+        disc_number,
         work,
         writer,
-        filename: filename.clone(),
+        format,
+        bitrate,
+        sample_rate,
+        bit_depth,
+        channels,
+        filename: filename.to_string(),
         dir,
-    }
+    })
 }
 
 #[cfg(test)]
@@ -347,7 +392,7 @@ mod tests {
     #[test]
     fn can_load_tag() {
         let tag =
-            load_song_tag(&"/home/andrew/music/adam-lambert/velvet/01-velvet.opus".to_string());
+            load_song_tag("/home/andrew/music/adam-lambert/velvet/01-velvet.opus").unwrap();
 
         assert_eq!(tag.track_artist, "Adam Lambert");
     }
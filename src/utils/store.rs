@@ -0,0 +1,201 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{
+    cache::{cache_file_path, CacheData, CacheEntry, CacheRecord, Fingerprint},
+    error::MicError,
+};
+
+const DB_NAME: &str = "mic.db";
+
+/// Bump whenever a table's columns change or records gain fields read from the files,
+/// older databases are dropped and rebuilt
+///
+/// 2 audio properties, 3 disc numbers, 4 covers without the pixels of their thumbnail
+const SCHEMA_VERSION: i32 = 4;
+
+/// SQLite database in `~/.cache/mic` holding every cache table
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open() -> Result<Store, MicError> {
+        let path = cache_file_path(DB_NAME)?;
+        let conn = Connection::open(&path).map_err(MicError::cache)?;
+        let store = Store { conn };
+
+        store.conn.pragma_update(None, "journal_mode", "WAL").map_err(MicError::cache)?;
+        store.migrate()?;
+
+        Ok(store)
+    }
+
+    /// Drops every table when the database was written by another schema version
+    fn migrate(&self) -> Result<(), MicError> {
+        let version: i32 = self
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(MicError::cache)?;
+
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        let tables: Vec<String> = self
+            .conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(MicError::cache)?;
+
+        for table in tables {
+            self.conn.execute(&format!("DROP TABLE IF EXISTS \"{}\"", table), []).map_err(MicError::cache)?;
+        }
+
+        self.conn.pragma_update(None, "user_version", SCHEMA_VERSION).map_err(MicError::cache)
+    }
+
+    fn ensure_table<T: CacheRecord>(&self) -> Result<(), MicError> {
+        let columns: Vec<String> = T::COLUMNS.iter().map(|c| format!("{} TEXT", c)).collect();
+        let mut sql = format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                filename TEXT PRIMARY KEY,
+                mtime INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                hash TEXT,
+                {columns},
+                data TEXT NOT NULL
+            );",
+            table = T::TABLE,
+            columns = columns.join(", "),
+        );
+
+        for column in T::COLUMNS {
+            sql.push_str(&format!(
+                "CREATE INDEX IF NOT EXISTS {table}_{column} ON {table} ({column});",
+                table = T::TABLE,
+                column = column,
+            ));
+        }
+
+        self.conn.execute_batch(&sql).map_err(MicError::cache)
+    }
+
+    pub fn load<T: CacheRecord>(&self) -> Result<CacheData<T>, MicError> {
+        self.load_where(|_, _| true)
+    }
+
+    /// Decodes only the rows `wanted` accepts from their filename and fingerprint, ex. files that didn't change,
+    /// the others are only listed so they can still be pruned
+    pub fn load_where<T, F>(&self, wanted: F) -> Result<CacheData<T>, MicError>
+    where
+        T: CacheRecord,
+        F: Fn(&str, &Fingerprint) -> bool,
+    {
+        self.ensure_table::<T>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT filename, mtime, size, hash, data FROM {}", T::TABLE))
+            .map_err(MicError::cache)?;
+        let mut rows = stmt.query([]).map_err(MicError::cache)?;
+        let mut cache = CacheData::new();
+
+        while let Some(row) = rows.next().map_err(MicError::cache)? {
+            let filename: String = row.get(0).map_err(MicError::cache)?;
+            let fingerprint = Fingerprint {
+                mtime: row.get::<_, i64>(1).map_err(MicError::cache)? as u64,
+                size: row.get::<_, i64>(2).map_err(MicError::cache)? as u64,
+                hash: row.get(3).map_err(MicError::cache)?,
+            };
+
+            // The data column is only read for wanted rows, decoding is most of the cost
+            if !wanted(&filename, &fingerprint) {
+                cache.add_unloaded(filename);
+                continue;
+            }
+
+            let data: String = row.get(4).map_err(MicError::cache)?;
+
+            match serde_json::from_str::<T>(&data) {
+                Ok(value) => {
+                    cache.data.insert(filename, CacheEntry { fingerprint, value });
+                },
+                Err(e) => {
+                    cache.unreadable.push(MicError::cache(format!("{}: entry can't be decoded, {}", filename, e)));
+                    cache.add_unloaded(filename);
+                },
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// Writes the entries changed since the cache was loaded in a single transaction
+    pub fn save<T: CacheRecord>(&mut self, cache: &CacheData<T>) -> Result<(), MicError> {
+        self.ensure_table::<T>()?;
+
+        let placeholders: Vec<String> = (0..T::COLUMNS.len() + 5).map(|i| format!("?{}", i + 1)).collect();
+        let insert = format!(
+            "INSERT OR REPLACE INTO {} (filename, mtime, size, hash, {}, data) VALUES ({})",
+            T::TABLE,
+            T::COLUMNS.join(", "),
+            placeholders.join(", "),
+        );
+        let delete = format!("DELETE FROM {} WHERE filename = ?1", T::TABLE);
+
+        let tx = self.conn.transaction().map_err(MicError::cache)?;
+
+        {
+            let mut delete_stmt = tx.prepare(&delete).map_err(MicError::cache)?;
+            for filename in cache.removed() {
+                delete_stmt.execute(params![filename]).map_err(MicError::cache)?;
+            }
+
+            let mut insert_stmt = tx.prepare(&insert).map_err(MicError::cache)?;
+            for filename in cache.changed() {
+                let entry = match cache.data.get(filename) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                let data = serde_json::to_string(&entry.value).map_err(MicError::cache)?;
+                let mut values: Vec<rusqlite::types::Value> = vec![
+                    filename.clone().into(),
+                    (entry.fingerprint.mtime as i64).into(),
+                    (entry.fingerprint.size as i64).into(),
+                    entry.fingerprint.hash.clone().into(),
+                ];
+
+                values.extend(entry.value.indexed_columns().into_iter().map(|v| v.into()));
+                values.push(data.into());
+
+                insert_stmt.execute(rusqlite::params_from_iter(values)).map_err(MicError::cache)?;
+            }
+        }
+
+        tx.commit().map_err(MicError::cache)
+    }
+
+    pub fn clear<T: CacheRecord>(&self) -> Result<(), MicError> {
+        self.ensure_table::<T>()?;
+        self.conn.execute(&format!("DELETE FROM {}", T::TABLE), []).map_err(MicError::cache)?;
+
+        Ok(())
+    }
+
+    /// Number of rows and bytes of serialized data stored for a cache
+    pub fn usage<T: CacheRecord>(&self) -> Result<(usize, u64), MicError> {
+        self.ensure_table::<T>()?;
+
+        let usage: Option<(i64, i64)> = self
+            .conn
+            .query_row(
+                &format!("SELECT COUNT(*), COALESCE(SUM(LENGTH(data)), 0) FROM {}", T::TABLE),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(MicError::cache)?;
+
+        Ok(usage.map(|(rows, bytes)| (rows as usize, bytes as u64)).unwrap_or_default())
+    }
+}
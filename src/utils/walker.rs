@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+use ignore::WalkBuilder;
+
+use super::{config::Config, data::validate_img_filename};
+
+/// Every audio file under the library roots, sorted by path
+pub fn walk_library(config: &Config) -> Vec<PathBuf> {
+    walk(config, |path| has_audio_extension(config, path))
+}
+
+/// Every cover image under the library roots, sorted by path
+pub fn walk_covers(config: &Config) -> Vec<PathBuf> {
+    walk(config, |path| is_cover_file(config, path))
+}
+
+fn walk<F>(config: &Config, keep: F) -> Vec<PathBuf>
+where
+    F: Fn(&Path) -> bool,
+{
+    let library = &config.library;
+    let layout = Pattern::new(&library.layout).ok();
+    let mut files = vec![];
+
+    for root in library.roots.iter() {
+        let mut builder = WalkBuilder::new(root);
+
+        builder
+            .follow_links(library.follow_symlinks)
+            .hidden(true)
+            .ignore(true)
+            .git_ignore(false)
+            .git_global(false)
+            .git_exclude(false)
+            .parents(false);
+
+        for name in library.ignore_files.iter() {
+            builder.add_custom_ignore_filename(name);
+        }
+
+        for entry in builder.build().filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+
+            if !entry.file_type().is_some_and(|t| t.is_file()) || !keep(path) {
+                continue;
+            }
+
+            if let Some(layout) = &layout {
+                if !matches_layout(layout, root, path) {
+                    continue;
+                }
+            }
+
+            files.push(path.to_path_buf());
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Checks the file's directory, relative to its root, against the layout glob
+fn matches_layout(layout: &Pattern, root: &Path, path: &Path) -> bool {
+    let dir = match path.parent().and_then(|p| p.strip_prefix(root).ok()) {
+        Some(dir) => dir,
+        None => return false,
+    };
+
+    layout.matches_path_with(
+        dir,
+        MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        },
+    )
+}
+
+fn has_audio_extension(config: &Config, path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => {
+            let ext = ext.to_string_lossy();
+
+            config.library.extensions.iter().any(|e| e.eq_ignore_ascii_case(&ext))
+        },
+        None => false,
+    }
+}
+
+fn is_cover_file(config: &Config, path: &Path) -> bool {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let name = path.file_name().map(|s| s.to_string_lossy()).unwrap_or_default();
+
+    config.library.cover_names.iter().any(|c| c.eq_ignore_ascii_case(&stem))
+        && validate_img_filename(&name).unwrap_or(false)
+}